@@ -0,0 +1,686 @@
+// `s3 archive` / `s3 unarchive`: stream a prefix into a tar (plain, gzip or
+// zstd) or zip archive, and expand such an archive back into a prefix.
+//
+// The tar/zip codecs are synchronous, so they run on a blocking thread and
+// reach S3 through the runtime handle. Nothing is staged on disk: object
+// bodies are read while the archive is written, and an S3 destination is
+// written as a multipart upload whose parts are uploaded concurrently.
+
+use crate::s3::{self, ListFilter};
+use aws_sdk_s3::Client as S3Client;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use futures::StreamExt;
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path};
+use std::pin::Pin;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Initial part size for multipart uploads of archives and large archive members.
+const PART_SIZE: usize = 16 * 1024 * 1024;
+/// S3 allows at most 10,000 parts per upload, so the part size doubles after
+/// every 1,000 parts (16 MiB up to 4 GiB), which keeps streams of unknown
+/// length uploadable up to S3's 5 TiB object limit.
+const MAX_PARTS: i32 = 10_000;
+const PARTS_PER_SIZE_STEP: i32 = 1_000;
+const MAX_SIZE_STEPS: i32 = 8;
+const UPLOAD_CONCURRENCY: usize = 8;
+/// Objects up to this size are downloaded ahead of the archive writer.
+const PREFETCH_MAX_SIZE: i64 = 1024 * 1024;
+const PREFETCH_CONCURRENCY: usize = 16;
+/// Window fetched per ranged GET when reading a zip's central directory and members from S3.
+const RANGE_CHUNK: u64 = 8 * 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+    Zip,
+}
+
+impl std::str::FromStr for ArchiveFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "tar.gz" | "tgz" | "gz" => Ok(ArchiveFormat::TarGz),
+            "tar.zst" | "tzst" | "zst" => Ok(ArchiveFormat::TarZst),
+            "zip" => Ok(ArchiveFormat::Zip),
+            _ => Err(format!("unknown archive format '{}' (use tar, tar.gz, tar.zst or zip)", s)),
+        }
+    }
+}
+
+impl ArchiveFormat {
+    /// Infer the format from a file name or key.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase();
+        [".tar.gz", ".tgz", ".tar.zst", ".tzst", ".tar", ".zip"]
+            .iter()
+            .find(|ext| name.ends_with(*ext))
+            .and_then(|ext| ext[1..].parse().ok())
+    }
+}
+
+/// Where an archive is read from or written to.
+#[derive(Clone)]
+pub enum Location {
+    Stdio,
+    File(String),
+    S3 { bucket: String, key: String },
+}
+
+impl Location {
+    pub fn parse(s: &str) -> Self {
+        if s == "-" {
+            Location::Stdio
+        } else if s.starts_with("s3://") {
+            let (bucket, key) = s3::parse_s3_path(s);
+            Location::S3 { bucket, key }
+        } else {
+            Location::File(s.to_string())
+        }
+    }
+
+    fn name(&self) -> &str {
+        match self {
+            Location::Stdio => "",
+            Location::File(path) => path,
+            Location::S3 { key, .. } => key,
+        }
+    }
+
+    /// The explicit format, or the one implied by the file name.
+    pub fn format(&self, explicit: Option<ArchiveFormat>) -> Result<ArchiveFormat, String> {
+        explicit
+            .or_else(|| ArchiveFormat::from_name(self.name()))
+            .ok_or_else(|| "cannot infer the archive format from the name; pass --format".to_string())
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Stdio => write!(f, "-"),
+            Location::File(path) => write!(f, "{}", path),
+            Location::S3 { bucket, key } => write!(f, "s3://{}/{}", bucket, key),
+        }
+    }
+}
+
+// ---- blocking adapters over S3 ----
+
+/// Blocking `Read` over a streaming object body.
+struct BodyReader {
+    handle: Handle,
+    inner: Pin<Box<dyn tokio::io::AsyncBufRead + Send>>,
+}
+
+impl BodyReader {
+    fn new(handle: Handle, body: ByteStream) -> Self {
+        BodyReader { handle, inner: Box::pin(body.into_async_read()) }
+    }
+}
+
+impl Read for BodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        use tokio::io::AsyncReadExt;
+        self.handle.block_on(self.inner.read(buf))
+    }
+}
+
+/// Blocking `Read + Seek` over an object using ranged GETs, for zip archives
+/// (whose directory lives at the end of the file).
+struct RangeReader {
+    handle: Handle,
+    client: S3Client,
+    bucket: String,
+    key: String,
+    len: u64,
+    pos: u64,
+    buf: Vec<u8>,
+    buf_start: u64,
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len || out.is_empty() {
+            return Ok(0);
+        }
+        let buffered = self.buf_start..self.buf_start + self.buf.len() as u64;
+        if !buffered.contains(&self.pos) {
+            let end = (self.pos + RANGE_CHUNK).min(self.len) - 1;
+            let req = self
+                .client
+                .get_object()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .range(format!("bytes={}-{}", self.pos, end));
+            self.buf = self.handle.block_on(async {
+                let resp = req.send().await.map_err(|e| io::Error::other(aws_sdk_s3::Error::from(e)))?;
+                let data = resp.body.collect().await.map_err(io::Error::other)?;
+                Ok::<_, io::Error>(data.into_bytes().to_vec())
+            })?;
+            self.buf_start = self.pos;
+            if self.buf.is_empty() {
+                return Ok(0);
+            }
+        }
+        let offset = (self.pos - self.buf_start) as usize;
+        let n = out.len().min(self.buf.len() - offset);
+        out[..n].copy_from_slice(&self.buf[offset..offset + n]);
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for RangeReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(p) => p as i64,
+            SeekFrom::End(d) => self.len as i64 + d,
+            SeekFrom::Current(d) => self.pos as i64 + d,
+        };
+        if target < 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "seek before start of object"));
+        }
+        self.pos = target as u64;
+        Ok(self.pos)
+    }
+}
+
+/// Blocking `Write` that uploads to S3: a single PutObject for small outputs,
+/// otherwise a multipart upload with up to `UPLOAD_CONCURRENCY` parts in
+/// flight. Dropping it without `finish` aborts the multipart upload.
+struct S3Writer {
+    handle: Handle,
+    client: S3Client,
+    bucket: String,
+    key: String,
+    upload_id: Option<String>,
+    buf: Vec<u8>,
+    next_part: i32,
+    in_flight: VecDeque<JoinHandle<Result<CompletedPart, aws_sdk_s3::Error>>>,
+    parts: Vec<CompletedPart>,
+}
+
+impl S3Writer {
+    fn new(handle: Handle, client: S3Client, bucket: &str, key: &str) -> Self {
+        S3Writer {
+            handle,
+            client,
+            bucket: bucket.to_string(),
+            key: key.to_string(),
+            upload_id: None,
+            buf: Vec::new(),
+            next_part: 1,
+            in_flight: VecDeque::new(),
+            parts: Vec::new(),
+        }
+    }
+
+    fn wait_oldest(&mut self) -> Result<(), BoxError> {
+        if let Some(task) = self.in_flight.pop_front() {
+            self.parts.push(self.handle.block_on(task)??);
+        }
+        Ok(())
+    }
+
+    /// Size of the next part to upload.
+    fn part_size(&self) -> usize {
+        PART_SIZE << ((self.next_part - 1) / PARTS_PER_SIZE_STEP).min(MAX_SIZE_STEPS)
+    }
+
+    fn upload_part(&mut self, data: Vec<u8>) -> Result<(), BoxError> {
+        if self.next_part > MAX_PARTS {
+            return Err(format!("s3://{}/{} exceeds the {} part limit of a multipart upload", self.bucket, self.key, MAX_PARTS).into());
+        }
+        let upload_id = match &self.upload_id {
+            Some(id) => id.clone(),
+            None => {
+                let req = self.client.create_multipart_upload().bucket(&self.bucket).key(&self.key);
+                let created = self.handle.block_on(req.send()).map_err(aws_sdk_s3::Error::from)?;
+                let id = created.upload_id().unwrap_or_default().to_string();
+                self.upload_id = Some(id.clone());
+                id
+            }
+        };
+        while self.in_flight.len() >= UPLOAD_CONCURRENCY {
+            self.wait_oldest()?;
+        }
+        let part_number = self.next_part;
+        self.next_part += 1;
+        let req = self
+            .client
+            .upload_part()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(upload_id)
+            .part_number(part_number)
+            .body(ByteStream::from(data));
+        self.in_flight.push_back(self.handle.spawn(async move {
+            let resp = req.send().await?;
+            Ok(CompletedPart::builder().part_number(part_number).set_e_tag(resp.e_tag().map(|t| t.to_string())).build())
+        }));
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<(), BoxError> {
+        let data = std::mem::take(&mut self.buf);
+        if self.upload_id.is_none() {
+            let req = self.client.put_object().bucket(&self.bucket).key(&self.key).body(ByteStream::from(data));
+            self.handle.block_on(req.send()).map_err(aws_sdk_s3::Error::from)?;
+            return Ok(());
+        }
+        if !data.is_empty() {
+            self.upload_part(data)?;
+        }
+        while !self.in_flight.is_empty() {
+            self.wait_oldest()?;
+        }
+        let mut parts = std::mem::take(&mut self.parts);
+        parts.sort_by_key(|p| p.part_number());
+        let req = self
+            .client
+            .complete_multipart_upload()
+            .bucket(&self.bucket)
+            .key(&self.key)
+            .upload_id(self.upload_id.clone().unwrap_or_default())
+            .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build());
+        self.handle.block_on(req.send()).map_err(aws_sdk_s3::Error::from)?;
+        self.upload_id = None;
+        Ok(())
+    }
+}
+
+impl Write for S3Writer {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        while self.buf.len() >= self.part_size() {
+            let rest = self.buf.split_off(self.part_size());
+            let part = std::mem::replace(&mut self.buf, rest);
+            self.upload_part(part).map_err(io::Error::other)?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for S3Writer {
+    fn drop(&mut self) {
+        if let Some(id) = self.upload_id.take() {
+            for task in self.in_flight.drain(..) {
+                task.abort();
+            }
+            let req = self.client.abort_multipart_upload().bucket(&self.bucket).key(&self.key).upload_id(id);
+            if let Err(e) = self.handle.block_on(req.send()) {
+                eprintln!("failed to abort upload of s3://{}/{}: {}", self.bucket, self.key, DisplayErrorContext(aws_sdk_s3::Error::from(e)));
+            }
+        }
+    }
+}
+
+// ---- archive ----
+
+/// One object on its way into the archive. Small bodies are fetched ahead of
+/// the writer; larger ones are streamed while they are written.
+struct Member {
+    name: String,
+    size: u64,
+    mtime: i64,
+    body: MemberBody,
+}
+
+enum MemberBody {
+    Buffered(Vec<u8>),
+    Streaming(ByteStream),
+}
+
+/// Members arriving from the async fetcher, consumed on the blocking thread.
+struct Members {
+    rx: tokio::sync::mpsc::Receiver<Result<Member, String>>,
+    handle: Handle,
+}
+
+impl Members {
+    fn next(&mut self) -> Option<Result<Member, BoxError>> {
+        self.rx.blocking_recv().map(|m| m.map_err(BoxError::from))
+    }
+
+    fn open(&self, body: MemberBody) -> Box<dyn Read> {
+        match body {
+            MemberBody::Buffered(data) => Box::new(io::Cursor::new(data)),
+            MemberBody::Streaming(stream) => Box::new(BodyReader::new(self.handle.clone(), stream)),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ArchiveStats {
+    pub objects: usize,
+    pub bytes: u64,
+}
+
+fn write_tar<W: Write>(out: W, members: &mut Members, stats: &mut ArchiveStats) -> Result<W, BoxError> {
+    let mut tar = tar::Builder::new(out);
+    while let Some(member) = members.next() {
+        let member = member?;
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_size(member.size);
+        header.set_mode(0o644);
+        header.set_mtime(member.mtime.max(0) as u64);
+        tar.append_data(&mut header, &member.name, members.open(member.body))?;
+        stats.objects += 1;
+        stats.bytes += member.size;
+    }
+    Ok(tar.into_inner()?)
+}
+
+/// Unix seconds to a zip (MS-DOS) timestamp in UTC; times zip cannot
+/// represent fall back to its epoch, 1980-01-01.
+fn zip_time(secs: i64) -> zip::DateTime {
+    // days-to-civil conversion from Howard Hinnant's date algorithms
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    zip::DateTime::from_date_and_time(
+        year.clamp(0, u16::MAX as i64) as u16,
+        month as u8,
+        day as u8,
+        (rem / 3600) as u8,
+        (rem % 3600 / 60) as u8,
+        (rem % 60) as u8,
+    )
+    .unwrap_or_default()
+}
+
+fn write_zip<W: Write>(out: W, members: &mut Members, stats: &mut ArchiveStats) -> Result<W, BoxError> {
+    let mut zip = zip::ZipWriter::new_stream(out);
+    while let Some(member) = members.next() {
+        let member = member?;
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .last_modified_time(zip_time(member.mtime))
+            .unix_permissions(0o644)
+            .large_file(member.size >= u32::MAX as u64);
+        zip.start_file(member.name.as_str(), options)?;
+        io::copy(&mut members.open(member.body), &mut zip)?;
+        stats.objects += 1;
+        stats.bytes += member.size;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+fn write_archive<W: Write>(out: W, format: ArchiveFormat, members: &mut Members, stats: &mut ArchiveStats) -> Result<W, BoxError> {
+    Ok(match format {
+        ArchiveFormat::Tar => write_tar(out, members, stats)?,
+        ArchiveFormat::TarGz => {
+            write_tar(flate2::write::GzEncoder::new(out, flate2::Compression::default()), members, stats)?.finish()?
+        }
+        ArchiveFormat::TarZst => write_tar(zstd::Encoder::new(out, 0)?, members, stats)?.finish()?,
+        ArchiveFormat::Zip => write_zip(out, members, stats)?,
+    })
+}
+
+async fn fetch_member(client: &S3Client, bucket: &str, key: &str, name: String, mtime: i64) -> Result<Member, String> {
+    let fail = |e: String| format!("s3://{}/{}: {}", bucket, key, e);
+    let resp = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .send()
+        .await
+        .map_err(|e| fail(DisplayErrorContext(aws_sdk_s3::Error::from(e)).to_string()))?;
+    let size = resp.content_length().unwrap_or(0);
+    let body = if size <= PREFETCH_MAX_SIZE {
+        MemberBody::Buffered(resp.body.collect().await.map_err(|e| fail(e.to_string()))?.into_bytes().to_vec())
+    } else {
+        MemberBody::Streaming(resp.body)
+    };
+    Ok(Member { name, size: size as u64, mtime, body })
+}
+
+/// `s3 archive`: write every object under `prefix` (narrowed by `filter`)
+/// into an archive at `dest`, named by its key relative to the prefix.
+/// Folder placeholder keys (ending in `/`) are skipped.
+pub async fn archive_prefix(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    filter: &ListFilter,
+    dest: &Location,
+    format: ArchiveFormat,
+) -> Result<ArchiveStats, BoxError> {
+    let objects: Vec<(String, String, i64)> = s3::list_objects_under(client, bucket, filter.effective_prefix(prefix))
+        .await?
+        .iter()
+        .filter(|o| filter.matches(o))
+        .filter_map(|o| {
+            let key = o.key()?;
+            if key.ends_with('/') {
+                return None;
+            }
+            let rel = key[prefix.len()..].trim_start_matches('/');
+            let name = if rel.is_empty() { key.rsplit('/').next().unwrap_or(key) } else { rel };
+            Some((key.to_string(), name.to_string(), o.last_modified().map(|t| t.secs()).unwrap_or(0)))
+        })
+        .collect();
+
+    let handle = Handle::current();
+    let (tx, rx) = tokio::sync::mpsc::channel(PREFETCH_CONCURRENCY);
+    let mut members = Members { rx, handle: handle.clone() };
+    let writer = {
+        let client = client.clone();
+        let dest = dest.clone();
+        tokio::task::spawn_blocking(move || -> Result<ArchiveStats, BoxError> {
+            let mut stats = ArchiveStats::default();
+            match dest {
+                Location::Stdio => {
+                    write_archive(io::stdout().lock(), format, &mut members, &mut stats)?.flush()?;
+                }
+                Location::File(path) => {
+                    let file = io::BufWriter::new(std::fs::File::create(&path)?);
+                    write_archive(file, format, &mut members, &mut stats)?.flush()?;
+                }
+                Location::S3 { bucket, key } => {
+                    let out = S3Writer::new(handle.clone(), client, &bucket, &key);
+                    write_archive(out, format, &mut members, &mut stats)?.finish()?;
+                }
+            }
+            Ok(stats)
+        })
+    };
+
+    let client = &s3::bulk_client(client);
+    let mut fetches = futures::stream::iter(objects)
+        .map(|(key, name, mtime)| async move { fetch_member(client, bucket, &key, name, mtime).await })
+        .buffered(PREFETCH_CONCURRENCY);
+    while let Some(member) = fetches.next().await {
+        let failed = member.is_err();
+        // A closed channel means the writer gave up; its error is reported below.
+        if tx.send(member).await.is_err() || failed {
+            break;
+        }
+    }
+    drop(tx);
+    writer.await?
+}
+
+// ---- unarchive ----
+
+/// Uploads archive members, several small ones at a time.
+struct Uploader {
+    handle: Handle,
+    client: S3Client,
+    bucket: String,
+    dry_run: bool,
+    in_flight: VecDeque<JoinHandle<Result<(), aws_sdk_s3::Error>>>,
+    uploaded: usize,
+}
+
+impl Uploader {
+    fn wait_oldest(&mut self) -> Result<(), BoxError> {
+        if let Some(task) = self.in_flight.pop_front() {
+            self.handle.block_on(task)??;
+            self.uploaded += 1;
+        }
+        Ok(())
+    }
+
+    fn upload(&mut self, key: String, size: u64, reader: &mut dyn Read) -> Result<(), BoxError> {
+        if self.dry_run {
+            println!("(dry run) upload: s3://{}/{}", self.bucket, key);
+            self.uploaded += 1;
+            return Ok(());
+        }
+        if size > PART_SIZE as u64 {
+            let mut out = S3Writer::new(self.handle.clone(), self.client.clone(), &self.bucket, &key);
+            io::copy(reader, &mut out)?;
+            out.finish()?;
+            println!("upload: s3://{}/{}", self.bucket, key);
+            self.uploaded += 1;
+            return Ok(());
+        }
+        let mut data = Vec::with_capacity(size as usize);
+        reader.read_to_end(&mut data)?;
+        while self.in_flight.len() >= UPLOAD_CONCURRENCY {
+            self.wait_oldest()?;
+        }
+        let req = self.client.put_object().bucket(&self.bucket).key(&key).body(ByteStream::from(data));
+        let bucket = self.bucket.clone();
+        self.in_flight.push_back(self.handle.spawn(async move {
+            req.send().await?;
+            println!("upload: s3://{}/{}", bucket, key);
+            Ok(())
+        }));
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<usize, BoxError> {
+        while !self.in_flight.is_empty() {
+            self.wait_oldest()?;
+        }
+        Ok(self.uploaded)
+    }
+}
+
+/// Destination key for an archive path, or None for paths that would escape
+/// the prefix (absolute or containing `..`).
+fn member_key(prefix: &str, path: &Path) -> Option<String> {
+    let mut parts = Vec::new();
+    for c in path.components() {
+        match c {
+            Component::Normal(s) => parts.push(s.to_string_lossy().into_owned()),
+            Component::CurDir => {}
+            _ => return None,
+        }
+    }
+    (!parts.is_empty()).then(|| format!("{}{}", prefix, parts.join("/")))
+}
+
+fn extract_tar<R: Read>(input: R, prefix: &str, uploader: &mut Uploader) -> Result<(), BoxError> {
+    let mut archive = tar::Archive::new(input);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path()?.into_owned();
+        let Some(key) = member_key(prefix, &path) else {
+            eprintln!("skipping unsafe path: {}", path.display());
+            continue;
+        };
+        let size = entry.size();
+        uploader.upload(key, size, &mut entry)?;
+    }
+    Ok(())
+}
+
+fn extract_zip<R: Read + Seek>(input: R, prefix: &str, uploader: &mut Uploader) -> Result<(), BoxError> {
+    let mut archive = zip::ZipArchive::new(input)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        if file.is_dir() {
+            continue;
+        }
+        let Some(key) = file.enclosed_name().and_then(|p| member_key(prefix, &p)) else {
+            eprintln!("skipping unsafe path: {}", file.name());
+            continue;
+        };
+        let size = file.size();
+        uploader.upload(key, size, &mut file)?;
+    }
+    Ok(())
+}
+
+fn extract<R: Read>(input: R, format: ArchiveFormat, prefix: &str, uploader: &mut Uploader) -> Result<(), BoxError> {
+    match format {
+        ArchiveFormat::Tar => extract_tar(input, prefix, uploader),
+        ArchiveFormat::TarGz => extract_tar(flate2::read::MultiGzDecoder::new(input), prefix, uploader),
+        ArchiveFormat::TarZst => extract_tar(zstd::Decoder::new(input)?, prefix, uploader),
+        ArchiveFormat::Zip => Err("zip archives must be read from a file or S3, not stdin".into()),
+    }
+}
+
+/// `s3 unarchive`: upload every regular file in the archive at `src` to
+/// `bucket` under `prefix`. Returns the number of objects uploaded.
+pub async fn unarchive(
+    client: &S3Client,
+    src: Location,
+    format: ArchiveFormat,
+    bucket: &str,
+    prefix: &str,
+    dry_run: bool,
+) -> Result<usize, BoxError> {
+    let handle = Handle::current();
+    let mut uploader = Uploader {
+        handle: handle.clone(),
+        client: s3::bulk_client(client),
+        bucket: bucket.to_string(),
+        dry_run,
+        in_flight: VecDeque::new(),
+        uploaded: 0,
+    };
+    let client = client.clone();
+    let prefix = prefix.to_string();
+    tokio::task::spawn_blocking(move || -> Result<usize, BoxError> {
+        match src {
+            Location::Stdio => extract(io::stdin().lock(), format, &prefix, &mut uploader)?,
+            Location::File(path) if format == ArchiveFormat::Zip => {
+                extract_zip(io::BufReader::new(std::fs::File::open(path)?), &prefix, &mut uploader)?
+            }
+            Location::File(path) => extract(io::BufReader::new(std::fs::File::open(path)?), format, &prefix, &mut uploader)?,
+            Location::S3 { bucket, key } if format == ArchiveFormat::Zip => {
+                let head = handle.block_on(client.head_object().bucket(&bucket).key(&key).send()).map_err(aws_sdk_s3::Error::from)?;
+                let len = head.content_length().unwrap_or(0) as u64;
+                let reader = RangeReader { handle: handle.clone(), client, bucket, key, len, pos: 0, buf: Vec::new(), buf_start: 0 };
+                extract_zip(reader, &prefix, &mut uploader)?
+            }
+            Location::S3 { bucket, key } => {
+                let resp = handle.block_on(client.get_object().bucket(&bucket).key(&key).send()).map_err(aws_sdk_s3::Error::from)?;
+                let body = io::BufReader::with_capacity(1024 * 1024, BodyReader::new(handle.clone(), resp.body));
+                extract(body, format, &prefix, &mut uploader)?
+            }
+        }
+        uploader.finish()
+    })
+    .await?
+}
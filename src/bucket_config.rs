@@ -0,0 +1,523 @@
+// Bucket configuration viewers/editors: lifecycle, CORS, policy, default
+// encryption and public access block.
+//
+// Documents use the same JSON shapes as the `aws s3api get-bucket-*` /
+// `put-bucket-*` commands, so existing files and console exports work as-is:
+// either the bare configuration (`{"Rules": [...]}`) or wrapped in its
+// envelope (`{"ServerSideEncryptionConfiguration": {...}}`,
+// `{"PublicAccessBlockConfiguration": {...}}`, `{"Policy": "..."}`, ...).
+// YAML files with the same structure are accepted too.
+
+use crate::json_doc::{bool_field, field, i32_field, i64_field, list_field, load_document, object, str_field, strings_field};
+use crate::s3::is_missing_config;
+use aws_sdk_s3::Client as S3Client;
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::types::{
+    AbortIncompleteMultipartUpload, BucketLifecycleConfiguration, CorsConfiguration, CorsRule, ExpirationStatus,
+    LifecycleExpiration, LifecycleRule, LifecycleRuleAndOperator, LifecycleRuleFilter, NoncurrentVersionExpiration,
+    NoncurrentVersionTransition, PublicAccessBlockConfiguration, ServerSideEncryption, ServerSideEncryptionByDefault,
+    ServerSideEncryptionConfiguration, ServerSideEncryptionRule, Tag, Transition, TransitionStorageClass,
+};
+use serde_json::{Value, json};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigKind {
+    Lifecycle,
+    Cors,
+    Policy,
+    Encryption,
+    PublicAccessBlock,
+}
+
+impl std::str::FromStr for ConfigKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lifecycle" => Ok(ConfigKind::Lifecycle),
+            "cors" => Ok(ConfigKind::Cors),
+            "policy" => Ok(ConfigKind::Policy),
+            "encryption" => Ok(ConfigKind::Encryption),
+            "public-access-block" => Ok(ConfigKind::PublicAccessBlock),
+            _ => Err(format!("unknown bucket configuration '{}'", s)),
+        }
+    }
+}
+
+impl ConfigKind {
+    /// The key wrapping this configuration in `get-*` output and
+    /// `put-* --cli-input-json` documents.
+    fn envelope(&self) -> &'static str {
+        match self {
+            ConfigKind::Lifecycle => "LifecycleConfiguration",
+            ConfigKind::Cors => "CORSConfiguration",
+            ConfigKind::Policy => "Policy",
+            ConfigKind::Encryption => "ServerSideEncryptionConfiguration",
+            ConfigKind::PublicAccessBlock => "PublicAccessBlockConfiguration",
+        }
+    }
+}
+
+/// Load a configuration document from `path`, unwrapping its envelope if present.
+pub fn load_config(kind: ConfigKind, path: &str) -> Result<Value, BoxError> {
+    let doc = load_document(path)?;
+    match field(&doc, kind.envelope()) {
+        // get-bucket-policy returns the policy as a JSON string.
+        Some(Value::String(policy)) if kind == ConfigKind::Policy => Ok(serde_json::from_str(policy)?),
+        Some(inner) => Ok(inner.clone()),
+        None => Ok(doc),
+    }
+}
+
+/// Fetch the current configuration as a document, or `None` if the bucket
+/// has none of this kind.
+pub async fn get_config(client: &S3Client, bucket: &str, kind: ConfigKind) -> Result<Option<Value>, aws_sdk_s3::Error> {
+    let res = match kind {
+        ConfigKind::Lifecycle => client
+            .get_bucket_lifecycle_configuration()
+            .bucket(bucket)
+            .send()
+            .await
+            .map(|r| lifecycle_to_json(r.rules()))
+            .map_err(aws_sdk_s3::Error::from),
+        ConfigKind::Cors => client
+            .get_bucket_cors()
+            .bucket(bucket)
+            .send()
+            .await
+            .map(|r| cors_to_json(r.cors_rules()))
+            .map_err(aws_sdk_s3::Error::from),
+        ConfigKind::Policy => client
+            .get_bucket_policy()
+            .bucket(bucket)
+            .send()
+            .await
+            .map(|r| {
+                let raw = r.policy().unwrap_or("{}");
+                serde_json::from_str(raw).unwrap_or_else(|_| Value::String(raw.to_string()))
+            })
+            .map_err(aws_sdk_s3::Error::from),
+        ConfigKind::Encryption => client
+            .get_bucket_encryption()
+            .bucket(bucket)
+            .send()
+            .await
+            .map(|r| encryption_to_json(r.server_side_encryption_configuration().map(|c| c.rules()).unwrap_or_default()))
+            .map_err(aws_sdk_s3::Error::from),
+        ConfigKind::PublicAccessBlock => client
+            .get_public_access_block()
+            .bucket(bucket)
+            .send()
+            .await
+            .map(|r| public_access_block_to_json(r.public_access_block_configuration()))
+            .map_err(aws_sdk_s3::Error::from),
+    };
+    match res {
+        Ok(v) => Ok(Some(v)),
+        Err(e) if is_missing_config(&e) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Validate a document by converting it to the SDK type and back. The result
+/// is what S3 would report after a put, so it diffs cleanly against `get_config`.
+pub fn normalize(kind: ConfigKind, doc: &Value) -> Result<Value, BoxError> {
+    Ok(match kind {
+        ConfigKind::Lifecycle => lifecycle_to_json(lifecycle_from_json(doc)?.rules()),
+        ConfigKind::Cors => cors_to_json(cors_from_json(doc)?.cors_rules()),
+        ConfigKind::Policy => doc.clone(),
+        ConfigKind::Encryption => encryption_to_json(encryption_from_json(doc)?.rules()),
+        ConfigKind::PublicAccessBlock => public_access_block_to_json(Some(&public_access_block_from_json(doc)?)),
+    })
+}
+
+pub async fn put_config(client: &S3Client, bucket: &str, kind: ConfigKind, doc: &Value) -> Result<(), BoxError> {
+    match kind {
+        ConfigKind::Lifecycle => {
+            client
+                .put_bucket_lifecycle_configuration()
+                .bucket(bucket)
+                .lifecycle_configuration(lifecycle_from_json(doc)?)
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+        ConfigKind::Cors => {
+            client
+                .put_bucket_cors()
+                .bucket(bucket)
+                .cors_configuration(cors_from_json(doc)?)
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+        ConfigKind::Policy => {
+            let policy = match doc {
+                Value::String(s) => s.clone(),
+                other => other.to_string(),
+            };
+            client
+                .put_bucket_policy()
+                .bucket(bucket)
+                .policy(policy)
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+        ConfigKind::Encryption => {
+            client
+                .put_bucket_encryption()
+                .bucket(bucket)
+                .server_side_encryption_configuration(encryption_from_json(doc)?)
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+        ConfigKind::PublicAccessBlock => {
+            client
+                .put_public_access_block()
+                .bucket(bucket)
+                .public_access_block_configuration(public_access_block_from_json(doc)?)
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from)?;
+        }
+    }
+    Ok(())
+}
+
+pub async fn delete_config(client: &S3Client, bucket: &str, kind: ConfigKind) -> Result<(), aws_sdk_s3::Error> {
+    match kind {
+        ConfigKind::Lifecycle => {
+            client.delete_bucket_lifecycle().bucket(bucket).send().await?;
+        }
+        ConfigKind::Cors => {
+            client.delete_bucket_cors().bucket(bucket).send().await?;
+        }
+        ConfigKind::Policy => {
+            client.delete_bucket_policy().bucket(bucket).send().await?;
+        }
+        ConfigKind::Encryption => {
+            client.delete_bucket_encryption().bucket(bucket).send().await?;
+        }
+        ConfigKind::PublicAccessBlock => {
+            client.delete_public_access_block().bucket(bucket).send().await?;
+        }
+    }
+    Ok(())
+}
+
+// ---- JSON field helpers ----
+
+fn date_field(v: &Value, name: &str) -> Result<Option<DateTime>, BoxError> {
+    match str_field(v, name)? {
+        Some(s) => Ok(Some(DateTime::from_str(&s, DateTimeFormat::DateTime)?)),
+        None => Ok(None),
+    }
+}
+
+fn date_json(d: Option<&DateTime>) -> Value {
+    d.and_then(|d| d.fmt(DateTimeFormat::DateTime).ok()).map(Value::from).unwrap_or(Value::Null)
+}
+
+fn tag_json(t: &Tag) -> Value {
+    json!({ "Key": t.key(), "Value": t.value() })
+}
+
+fn tag_from_json(v: &Value) -> Result<Tag, BoxError> {
+    Ok(Tag::builder()
+        .set_key(str_field(v, "Key")?)
+        .set_value(str_field(v, "Value")?)
+        .build()?)
+}
+
+// ---- lifecycle ----
+
+// Rule-level `Prefix` is deprecated in favour of `Filter`, but older rules
+// still use it, so it is round-tripped rather than dropped.
+#[allow(deprecated)]
+fn lifecycle_to_json(rules: &[LifecycleRule]) -> Value {
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|r| {
+            let filter = r.filter().map(|f| {
+                object(vec![
+                    ("Prefix", f.prefix().into()),
+                    ("Tag", f.tag().map(tag_json).unwrap_or(Value::Null)),
+                    ("ObjectSizeGreaterThan", f.object_size_greater_than().into()),
+                    ("ObjectSizeLessThan", f.object_size_less_than().into()),
+                    (
+                        "And",
+                        f.and()
+                            .map(|a| {
+                                object(vec![
+                                    ("Prefix", a.prefix().into()),
+                                    ("Tags", Value::Array(a.tags().iter().map(tag_json).collect())),
+                                    ("ObjectSizeGreaterThan", a.object_size_greater_than().into()),
+                                    ("ObjectSizeLessThan", a.object_size_less_than().into()),
+                                ])
+                            })
+                            .unwrap_or(Value::Null),
+                    ),
+                ])
+            });
+            object(vec![
+                ("ID", r.id().into()),
+                ("Status", r.status().as_str().into()),
+                ("Prefix", r.prefix().into()),
+                ("Filter", filter.unwrap_or(Value::Null)),
+                (
+                    "Expiration",
+                    r.expiration()
+                        .map(|e| {
+                            object(vec![
+                                ("Days", e.days().into()),
+                                ("Date", date_json(e.date())),
+                                ("ExpiredObjectDeleteMarker", e.expired_object_delete_marker().into()),
+                            ])
+                        })
+                        .unwrap_or(Value::Null),
+                ),
+                (
+                    "Transitions",
+                    Value::Array(
+                        r.transitions()
+                            .iter()
+                            .map(|t| {
+                                object(vec![
+                                    ("Days", t.days().into()),
+                                    ("Date", date_json(t.date())),
+                                    ("StorageClass", t.storage_class().map(|c| c.as_str()).into()),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "NoncurrentVersionTransitions",
+                    Value::Array(
+                        r.noncurrent_version_transitions()
+                            .iter()
+                            .map(|t| {
+                                object(vec![
+                                    ("NoncurrentDays", t.noncurrent_days().into()),
+                                    ("StorageClass", t.storage_class().map(|c| c.as_str()).into()),
+                                    ("NewerNoncurrentVersions", t.newer_noncurrent_versions().into()),
+                                ])
+                            })
+                            .collect(),
+                    ),
+                ),
+                (
+                    "NoncurrentVersionExpiration",
+                    r.noncurrent_version_expiration()
+                        .map(|e| {
+                            object(vec![
+                                ("NoncurrentDays", e.noncurrent_days().into()),
+                                ("NewerNoncurrentVersions", e.newer_noncurrent_versions().into()),
+                            ])
+                        })
+                        .unwrap_or(Value::Null),
+                ),
+                (
+                    "AbortIncompleteMultipartUpload",
+                    r.abort_incomplete_multipart_upload()
+                        .map(|a| object(vec![("DaysAfterInitiation", a.days_after_initiation().into())]))
+                        .unwrap_or(Value::Null),
+                ),
+            ])
+        })
+        .collect();
+    json!({ "Rules": rules })
+}
+
+#[allow(deprecated)]
+fn lifecycle_from_json(doc: &Value) -> Result<BucketLifecycleConfiguration, BoxError> {
+    let mut rules = Vec::new();
+    for r in list_field(doc, "Rules")? {
+        let mut rule = LifecycleRule::builder()
+            .set_id(str_field(r, "ID")?)
+            .set_prefix(str_field(r, "Prefix")?)
+            .status(ExpirationStatus::from(str_field(r, "Status")?.unwrap_or_else(|| "Enabled".into()).as_str()));
+
+        if let Some(f) = field(r, "Filter") {
+            let and = match field(f, "And") {
+                Some(a) => Some(
+                    LifecycleRuleAndOperator::builder()
+                        .set_prefix(str_field(a, "Prefix")?)
+                        .set_tags(Some(list_field(a, "Tags")?.iter().map(tag_from_json).collect::<Result<Vec<_>, _>>()?))
+                        .set_object_size_greater_than(i64_field(a, "ObjectSizeGreaterThan")?)
+                        .set_object_size_less_than(i64_field(a, "ObjectSizeLessThan")?)
+                        .build(),
+                ),
+                None => None,
+            };
+            rule = rule.filter(
+                LifecycleRuleFilter::builder()
+                    .set_prefix(str_field(f, "Prefix")?)
+                    .set_tag(field(f, "Tag").map(tag_from_json).transpose()?)
+                    .set_object_size_greater_than(i64_field(f, "ObjectSizeGreaterThan")?)
+                    .set_object_size_less_than(i64_field(f, "ObjectSizeLessThan")?)
+                    .set_and(and)
+                    .build(),
+            );
+        }
+        if let Some(e) = field(r, "Expiration") {
+            rule = rule.expiration(
+                LifecycleExpiration::builder()
+                    .set_days(i32_field(e, "Days")?)
+                    .set_date(date_field(e, "Date")?)
+                    .set_expired_object_delete_marker(bool_field(e, "ExpiredObjectDeleteMarker")?)
+                    .build(),
+            );
+        }
+        for t in list_field(r, "Transitions")? {
+            rule = rule.transitions(
+                Transition::builder()
+                    .set_days(i32_field(t, "Days")?)
+                    .set_date(date_field(t, "Date")?)
+                    .set_storage_class(str_field(t, "StorageClass")?.map(|c| TransitionStorageClass::from(c.as_str())))
+                    .build(),
+            );
+        }
+        for t in list_field(r, "NoncurrentVersionTransitions")? {
+            rule = rule.noncurrent_version_transitions(
+                NoncurrentVersionTransition::builder()
+                    .set_noncurrent_days(i32_field(t, "NoncurrentDays")?)
+                    .set_storage_class(str_field(t, "StorageClass")?.map(|c| TransitionStorageClass::from(c.as_str())))
+                    .set_newer_noncurrent_versions(i32_field(t, "NewerNoncurrentVersions")?)
+                    .build(),
+            );
+        }
+        if let Some(e) = field(r, "NoncurrentVersionExpiration") {
+            rule = rule.noncurrent_version_expiration(
+                NoncurrentVersionExpiration::builder()
+                    .set_noncurrent_days(i32_field(e, "NoncurrentDays")?)
+                    .set_newer_noncurrent_versions(i32_field(e, "NewerNoncurrentVersions")?)
+                    .build(),
+            );
+        }
+        if let Some(a) = field(r, "AbortIncompleteMultipartUpload") {
+            rule = rule.abort_incomplete_multipart_upload(
+                AbortIncompleteMultipartUpload::builder()
+                    .set_days_after_initiation(i32_field(a, "DaysAfterInitiation")?)
+                    .build(),
+            );
+        }
+        rules.push(rule.build()?);
+    }
+    Ok(BucketLifecycleConfiguration::builder().set_rules(Some(rules)).build()?)
+}
+
+// ---- CORS ----
+
+fn cors_to_json(rules: &[CorsRule]) -> Value {
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|r| {
+            object(vec![
+                ("ID", r.id().into()),
+                ("AllowedHeaders", r.allowed_headers().into()),
+                ("AllowedMethods", r.allowed_methods().into()),
+                ("AllowedOrigins", r.allowed_origins().into()),
+                ("ExposeHeaders", r.expose_headers().into()),
+                ("MaxAgeSeconds", r.max_age_seconds().into()),
+            ])
+        })
+        .collect();
+    json!({ "CORSRules": rules })
+}
+
+fn cors_from_json(doc: &Value) -> Result<CorsConfiguration, BoxError> {
+    let rules = list_field(doc, "CORSRules")?
+        .iter()
+        .map(|r| {
+            Ok(CorsRule::builder()
+                .set_id(str_field(r, "ID")?)
+                .set_allowed_headers(strings_field(r, "AllowedHeaders")?)
+                .set_allowed_methods(strings_field(r, "AllowedMethods")?)
+                .set_allowed_origins(strings_field(r, "AllowedOrigins")?)
+                .set_expose_headers(strings_field(r, "ExposeHeaders")?)
+                .set_max_age_seconds(i32_field(r, "MaxAgeSeconds")?)
+                .build()?)
+        })
+        .collect::<Result<Vec<_>, BoxError>>()?;
+    Ok(CorsConfiguration::builder().set_cors_rules(Some(rules)).build()?)
+}
+
+// ---- default encryption ----
+
+fn encryption_to_json(rules: &[ServerSideEncryptionRule]) -> Value {
+    let rules: Vec<Value> = rules
+        .iter()
+        .map(|r| {
+            object(vec![
+                (
+                    "ApplyServerSideEncryptionByDefault",
+                    r.apply_server_side_encryption_by_default()
+                        .map(|d| {
+                            object(vec![
+                                ("SSEAlgorithm", d.sse_algorithm().as_str().into()),
+                                ("KMSMasterKeyID", d.kms_master_key_id().into()),
+                            ])
+                        })
+                        .unwrap_or(Value::Null),
+                ),
+                ("BucketKeyEnabled", r.bucket_key_enabled().into()),
+            ])
+        })
+        .collect();
+    json!({ "Rules": rules })
+}
+
+fn encryption_from_json(doc: &Value) -> Result<ServerSideEncryptionConfiguration, BoxError> {
+    let mut rules = Vec::new();
+    for r in list_field(doc, "Rules")? {
+        let default = match field(r, "ApplyServerSideEncryptionByDefault") {
+            Some(d) => Some(
+                ServerSideEncryptionByDefault::builder()
+                    .set_sse_algorithm(str_field(d, "SSEAlgorithm")?.map(|a| ServerSideEncryption::from(a.as_str())))
+                    .set_kms_master_key_id(str_field(d, "KMSMasterKeyID")?)
+                    .build()?,
+            ),
+            None => None,
+        };
+        rules.push(
+            ServerSideEncryptionRule::builder()
+                .set_apply_server_side_encryption_by_default(default)
+                .set_bucket_key_enabled(bool_field(r, "BucketKeyEnabled")?)
+                .build(),
+        );
+    }
+    Ok(ServerSideEncryptionConfiguration::builder().set_rules(Some(rules)).build()?)
+}
+
+// ---- public access block ----
+
+fn public_access_block_to_json(cfg: Option<&PublicAccessBlockConfiguration>) -> Value {
+    let flag = |f: fn(&PublicAccessBlockConfiguration) -> Option<bool>| cfg.and_then(f).unwrap_or(false);
+    json!({
+        "BlockPublicAcls": flag(|c| c.block_public_acls()),
+        "IgnorePublicAcls": flag(|c| c.ignore_public_acls()),
+        "BlockPublicPolicy": flag(|c| c.block_public_policy()),
+        "RestrictPublicBuckets": flag(|c| c.restrict_public_buckets()),
+    })
+}
+
+/// Flags missing from the document default to false, matching S3's behaviour
+/// on put, but at least one must be present: an empty or mis-shaped document
+/// would otherwise silently switch every block off.
+fn public_access_block_from_json(doc: &Value) -> Result<PublicAccessBlockConfiguration, BoxError> {
+    const FLAGS: [&str; 4] = ["BlockPublicAcls", "IgnorePublicAcls", "BlockPublicPolicy", "RestrictPublicBuckets"];
+    if !FLAGS.iter().any(|f| field(doc, f).is_some()) {
+        return Err(format!("public access block document sets none of {}", FLAGS.join(", ")).into());
+    }
+    Ok(PublicAccessBlockConfiguration::builder()
+        .block_public_acls(bool_field(doc, "BlockPublicAcls")?.unwrap_or(false))
+        .ignore_public_acls(bool_field(doc, "IgnorePublicAcls")?.unwrap_or(false))
+        .block_public_policy(bool_field(doc, "BlockPublicPolicy")?.unwrap_or(false))
+        .restrict_public_buckets(bool_field(doc, "RestrictPublicBuckets")?.unwrap_or(false))
+        .build())
+}
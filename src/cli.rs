@@ -0,0 +1,229 @@
+// Small helpers for the hand-rolled argument parsing in main.rs.
+//
+// Positional arguments are still read in order by each command; these helpers
+// pull `--flag` / `--opt value` / `--opt=value` style options out of the
+// remaining argument list first so they can appear anywhere on the line.
+
+/// Remove a boolean `--flag` from `args`, returning whether it was present.
+pub fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|a| a != name);
+    args.len() != before
+}
+
+/// Remove `--name value` or `--name=value` from `args` and return the value.
+/// If the option is given more than once the last occurrence wins.
+pub fn take_opt(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let prefix = format!("{}=", name);
+    let mut value = None;
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == name {
+            args.remove(i);
+            if i < args.len() {
+                value = Some(args.remove(i));
+            }
+        } else if let Some(v) = args[i].strip_prefix(&prefix) {
+            value = Some(v.to_string());
+            args.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+    value
+}
+
+/// Parse a duration such as `90`, `30s`, `15m`, `1h` or `7d` (bare numbers are seconds).
+pub fn parse_duration(s: &str) -> Result<std::time::Duration, String> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, "s"),
+    };
+    let n: u64 = num.parse().map_err(|_| format!("invalid duration: {}", s))?;
+    let mult = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 60 * 60 * 24,
+        _ => return Err(format!("invalid duration unit in '{}' (use s, m, h or d)", s)),
+    };
+    let secs = n.checked_mul(mult).ok_or_else(|| format!("duration too large: {}", s))?;
+    Ok(std::time::Duration::from_secs(secs))
+}
+
+/// Remove every `--include PATTERN` / `--exclude PATTERN` from `args`, keeping
+/// their relative order (`true` = include). Later rules take precedence.
+pub fn take_filters(args: &mut Vec<String>) -> Vec<(bool, String)> {
+    let mut rules = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        let include = match args[i].as_str() {
+            "--include" => true,
+            "--exclude" => false,
+            _ => {
+                i += 1;
+                continue;
+            }
+        };
+        args.remove(i);
+        if i < args.len() {
+            rules.push((include, args.remove(i)));
+        }
+    }
+    rules
+}
+
+/// Remove every occurrence of the options in `names` (each followed by a
+/// value) from `args`, returning `(name, value)` pairs in command-line order.
+pub fn take_opts_ordered(args: &mut Vec<String>, names: &[&str]) -> Vec<(String, String)> {
+    let mut found = Vec::new();
+    let mut i = 0;
+    while i < args.len() {
+        if !names.contains(&args[i].as_str()) {
+            i += 1;
+            continue;
+        }
+        let name = args.remove(i);
+        if i < args.len() {
+            found.push((name, args.remove(i)));
+        }
+    }
+    found
+}
+
+/// Ask a yes/no style question on stderr; returns true only if the answer
+/// read from stdin equals `expected` (after trimming).
+pub fn confirm(prompt: &str, expected: &str) -> bool {
+    read_answer(prompt).is_some_and(|a| a == expected)
+}
+
+fn read_answer(prompt: &str) -> Option<String> {
+    use std::io::Write;
+    eprint!("{}", prompt);
+    let _ = std::io::stderr().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).ok()?;
+    Some(answer.trim().to_string())
+}
+
+/// Parse a byte size such as `1048576`, `500k`, `10MB` or `1.5G` (binary units).
+pub fn parse_size(s: &str) -> Result<i64, String> {
+    let t = s.trim();
+    let split = t.find(|c: char| !(c.is_ascii_digit() || c == '.')).unwrap_or(t.len());
+    let (num, unit) = t.split_at(split);
+    let n: f64 = num.parse().map_err(|_| format!("invalid size: {}", s))?;
+    let mult: f64 = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" => 1.0,
+        "k" | "kb" | "kib" => 1024.0,
+        "m" | "mb" | "mib" => 1024.0 * 1024.0,
+        "g" | "gb" | "gib" => 1024.0 * 1024.0 * 1024.0,
+        "t" | "tb" | "tib" => 1024.0 * 1024.0 * 1024.0 * 1024.0,
+        _ => return Err(format!("invalid size unit in '{}' (use k, M, G or T)", s)),
+    };
+    Ok((n * mult) as i64)
+}
+
+/// Parse a point in time: an RFC 3339 timestamp, a `YYYY-MM-DD` date (UTC
+/// midnight), or a duration like `7d` meaning that long before now.
+pub fn parse_time(s: &str) -> Result<aws_sdk_s3::primitives::DateTime, String> {
+    use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+    let t = s.trim();
+    if let Ok(d) = DateTime::from_str(t, DateTimeFormat::DateTime) {
+        return Ok(d);
+    }
+    if let Ok(d) = DateTime::from_str(&format!("{}T00:00:00Z", t), DateTimeFormat::DateTime) {
+        return Ok(d);
+    }
+    let ago = parse_duration(t).map_err(|_| format!("invalid time '{}' (use 2024-01-31, RFC 3339 or e.g. 7d)", s))?;
+    let then = std::time::SystemTime::now().checked_sub(ago).ok_or_else(|| format!("time too far in the past: {}", s))?;
+    Ok(DateTime::from(then))
+}
+
+/// Ask `prompt [y/N]` on stderr; true only for an explicit `y` / `yes`.
+pub fn confirm_yes(prompt: &str) -> bool {
+    read_answer(&format!("{} [y/N] ", prompt))
+        .is_some_and(|a| a.eq_ignore_ascii_case("y") || a.eq_ignore_ascii_case("yes"))
+}
+
+/// Read non-empty lines from a file, or from stdin when `path` is `-`.
+pub fn read_lines(path: &str) -> std::io::Result<Vec<String>> {
+    let text = if path == "-" {
+        std::io::read_to_string(std::io::stdin())?
+    } else {
+        std::fs::read_to_string(path)?
+    };
+    Ok(text.lines().map(|l| l.trim_end_matches('\r')).filter(|l| !l.is_empty()).map(String::from).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn duration_units() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30s"), Ok(Duration::from_secs(30)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(15 * 60)));
+        assert_eq!(parse_duration(" 1h "), Ok(Duration::from_secs(3600)));
+        assert_eq!(parse_duration("7d"), Ok(Duration::from_secs(7 * 86400)));
+    }
+
+    #[test]
+    fn duration_errors() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("5w").is_err());
+        assert!(parse_duration("1.5h").is_err());
+        assert!(parse_duration("-5m").is_err());
+        assert!(parse_duration("99999999999999999999").is_err());
+        assert!(parse_duration(&format!("{}d", u64::MAX / 1000)).is_err());
+    }
+
+    #[test]
+    fn size_units() {
+        assert_eq!(parse_size("1048576"), Ok(1048576));
+        assert_eq!(parse_size("500k"), Ok(500 * 1024));
+        assert_eq!(parse_size("10MB"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1.5G"), Ok(3 * 512 * 1024 * 1024));
+        assert_eq!(parse_size("2 TiB"), Ok(2 << 40));
+        assert_eq!(parse_size("7b"), Ok(7));
+    }
+
+    #[test]
+    fn size_errors() {
+        assert!(parse_size("").is_err());
+        assert!(parse_size("MB").is_err());
+        assert!(parse_size("10x").is_err());
+        assert!(parse_size("1.2.3k").is_err());
+    }
+
+    #[test]
+    fn time_formats() {
+        let t = parse_time("2024-01-31").unwrap();
+        assert_eq!(t.secs(), 1706659200);
+        let t = parse_time("2024-01-31T12:30:00Z").unwrap();
+        assert_eq!(t.secs(), 1706659200 + 12 * 3600 + 30 * 60);
+        let now = aws_sdk_s3::primitives::DateTime::from(std::time::SystemTime::now()).secs();
+        let ago = parse_time("1d").unwrap().secs();
+        assert!((now - 86400 - ago).abs() <= 1);
+    }
+
+    #[test]
+    fn time_errors() {
+        assert!(parse_time("yesterday").is_err());
+        assert!(parse_time("2024-13-45").is_err());
+        assert!(parse_time(&format!("{}d", u64::MAX / 86400)).is_err());
+    }
+
+    #[test]
+    fn options_anywhere() {
+        let mut args: Vec<String> = ["a", "--json", "--region=eu-west-1", "b", "--limit", "5"].iter().map(|s| s.to_string()).collect();
+        assert!(take_flag(&mut args, "--json"));
+        assert!(!take_flag(&mut args, "--json"));
+        assert_eq!(take_opt(&mut args, "--region").as_deref(), Some("eu-west-1"));
+        assert_eq!(take_opt(&mut args, "--limit").as_deref(), Some("5"));
+        assert_eq!(args, ["a", "b"]);
+    }
+}
@@ -0,0 +1,52 @@
+// Minimal line diff used to preview configuration changes before applying them.
+
+/// Diff `old` against `new` line by line (longest common subsequence) and
+/// return the lines prefixed with `  ` (unchanged), `- ` (removed) or `+ ` (added).
+pub fn line_diff(old: &str, new: &str) -> Vec<String> {
+    let a: Vec<&str> = old.lines().collect();
+    let b: Vec<&str> = new.lines().collect();
+
+    // lcs[i][j] = length of the LCS of a[i..] and b[j..]
+    let mut lcs = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut out = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            out.push(format!("  {}", a[i]));
+            i += 1;
+            j += 1;
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            out.push(format!("- {}", a[i]));
+            i += 1;
+        } else {
+            out.push(format!("+ {}", b[j]));
+            j += 1;
+        }
+    }
+    out.extend(a[i..].iter().map(|l| format!("- {}", l)));
+    out.extend(b[j..].iter().map(|l| format!("+ {}", l)));
+    out
+}
+
+/// Print a diff of `old` → `new`; returns false (and prints nothing but a
+/// note) when they are identical.
+pub fn print_diff(old: &str, new: &str) -> bool {
+    if old == new {
+        println!("No changes.");
+        return false;
+    }
+    for line in line_diff(old, new) {
+        println!("{}", line);
+    }
+    true
+}
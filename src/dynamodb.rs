@@ -1,32 +1,330 @@
 use aws_sdk_dynamodb::Client as DdbClient;
-use aws_sdk_dynamodb::types::AttributeValue;
+use aws_sdk_dynamodb::error::DisplayErrorContext;
+use aws_sdk_dynamodb::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_dynamodb::types::{
+    AttributeValue, BillingMode, ContinuousBackupsDescription, KeySchemaElement, OnDemandThroughput, Projection,
+    ProvisionedThroughputDescription, TableDescription, TimeToLiveDescription,
+};
+use futures::StreamExt;
+use serde_json::{Map, Value, json};
 use std::collections::{HashMap, HashSet};
 
+fn fmt_time(t: &DateTime) -> String {
+    t.fmt(DateTimeFormat::DateTime).unwrap_or_else(|_| t.secs().to_string())
+}
+
+fn key_schema_json(keys: &[KeySchemaElement]) -> Value {
+    keys.iter()
+        .map(|k| json!({ "AttributeName": k.attribute_name(), "KeyType": k.key_type().as_str() }))
+        .collect()
+}
+
+/// `pk HASH, sk RANGE`
+fn key_schema_text(keys: &[KeySchemaElement]) -> String {
+    keys.iter()
+        .map(|k| format!("{} {}", k.attribute_name(), k.key_type().as_str()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn projection_json(p: Option<&Projection>) -> Value {
+    let Some(p) = p else { return Value::Null };
+    let mut v = json!({ "ProjectionType": p.projection_type().map(|t| t.as_str()) });
+    if !p.non_key_attributes().is_empty() {
+        v["NonKeyAttributes"] = json!(p.non_key_attributes());
+    }
+    v
+}
+
+fn projection_text(p: Option<&Projection>) -> String {
+    match p {
+        Some(p) if !p.non_key_attributes().is_empty() => format!(
+            "{} [{}]",
+            p.projection_type().map(|t| t.as_str()).unwrap_or("?"),
+            p.non_key_attributes().join(", ")
+        ),
+        Some(p) => p.projection_type().map(|t| t.as_str()).unwrap_or("?").to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn throughput_json(p: Option<&ProvisionedThroughputDescription>) -> Value {
+    match p {
+        Some(p) => json!({ "ReadCapacityUnits": p.read_capacity_units(), "WriteCapacityUnits": p.write_capacity_units() }),
+        None => Value::Null,
+    }
+}
+
+fn on_demand_json(p: Option<&OnDemandThroughput>) -> Value {
+    match p {
+        Some(p) => json!({ "MaxReadRequestUnits": p.max_read_request_units(), "MaxWriteRequestUnits": p.max_write_request_units() }),
+        None => Value::Null,
+    }
+}
+
+/// Capacity line for a table or GSI: on-demand (with any max request units) or provisioned RCU/WCU.
+fn capacity_text(on_demand: bool, provisioned: Option<&ProvisionedThroughputDescription>, max: Option<&OnDemandThroughput>) -> String {
+    if on_demand {
+        match max.map(|m| (m.max_read_request_units(), m.max_write_request_units())) {
+            Some((r, w)) if r.is_some() || w.is_some() => format!(
+                "on-demand (max read {}, max write {})",
+                r.map(|n| n.to_string()).unwrap_or_else(|| "-".into()),
+                w.map(|n| n.to_string()).unwrap_or_else(|| "-".into())
+            ),
+            _ => "on-demand".to_string(),
+        }
+    } else {
+        let p = provisioned;
+        format!(
+            "provisioned (read {}, write {})",
+            p.and_then(|p| p.read_capacity_units()).unwrap_or(0),
+            p.and_then(|p| p.write_capacity_units()).unwrap_or(0)
+        )
+    }
+}
+
+pub async fn list_table_tags(client: &DdbClient, arn: &str) -> Result<Vec<(String, String)>, aws_sdk_dynamodb::Error> {
+    let mut tags = Vec::new();
+    let mut next_token: Option<String> = None;
+    loop {
+        let resp = client.list_tags_of_resource().resource_arn(arn).set_next_token(next_token).send().await?;
+        tags.extend(resp.tags().iter().map(|t| (t.key().to_string(), t.value().to_string())));
+        next_token = resp.next_token().map(|t| t.to_string());
+        if next_token.is_none() {
+            break;
+        }
+    }
+    Ok(tags)
+}
+
+/// Settings that live outside DescribeTable. Each is optional: some
+/// endpoints (and restricted IAM roles) don't support every call.
+struct TableExtras {
+    ttl: Option<TimeToLiveDescription>,
+    backups: Option<ContinuousBackupsDescription>,
+    tags: Option<Vec<(String, String)>>,
+}
+
+async fn describe_table_extras(client: &DdbClient, table: &str, arn: &str) -> TableExtras {
+    fn note<T, E: Into<aws_sdk_dynamodb::Error>>(what: &str, r: Result<T, E>) -> Option<T> {
+        r.map_err(|e| eprintln!("note: could not read {}: {}", what, DisplayErrorContext(e.into()))).ok()
+    }
+    let (ttl, backups, tags) = futures::join!(
+        client.describe_time_to_live().table_name(table).send(),
+        client.describe_continuous_backups().table_name(table).send(),
+        list_table_tags(client, arn),
+    );
+    TableExtras {
+        ttl: note("TTL", ttl).and_then(|r| r.time_to_live_description),
+        backups: note("continuous backups", backups).and_then(|r| r.continuous_backups_description),
+        tags: note("tags", tags),
+    }
+}
+
+/// The full description in the shape `aws dynamodb describe-table` uses,
+/// plus `TimeToLive`, `PointInTimeRecovery` and `Tags` sections.
+fn table_json(t: &TableDescription, extras: &TableExtras) -> Value {
+    let billing = t.billing_mode_summary().and_then(|b| b.billing_mode()).map(|m| m.as_str()).unwrap_or("PROVISIONED");
+    let mut v = json!({
+        "TableName": t.table_name(),
+        "TableStatus": t.table_status().map(|s| s.as_str()),
+        "TableArn": t.table_arn(),
+        "CreationDateTime": t.creation_date_time().map(fmt_time),
+        "ItemCount": t.item_count(),
+        "TableSizeBytes": t.table_size_bytes(),
+        "BillingMode": billing,
+        "ProvisionedThroughput": throughput_json(t.provisioned_throughput()),
+        "OnDemandThroughput": on_demand_json(t.on_demand_throughput()),
+        "TableClass": t.table_class_summary().and_then(|c| c.table_class()).map(|c| c.as_str()),
+        "DeletionProtectionEnabled": t.deletion_protection_enabled(),
+        "AttributeDefinitions": t.attribute_definitions().iter()
+            .map(|a| json!({ "AttributeName": a.attribute_name(), "AttributeType": a.attribute_type().as_str() }))
+            .collect::<Vec<_>>(),
+        "KeySchema": key_schema_json(t.key_schema()),
+    });
+    if !t.global_secondary_indexes().is_empty() {
+        v["GlobalSecondaryIndexes"] = t.global_secondary_indexes().iter()
+            .map(|g| json!({
+                "IndexName": g.index_name(),
+                "IndexStatus": g.index_status().map(|s| s.as_str()),
+                "KeySchema": key_schema_json(g.key_schema()),
+                "Projection": projection_json(g.projection()),
+                "ProvisionedThroughput": throughput_json(g.provisioned_throughput()),
+                "OnDemandThroughput": on_demand_json(g.on_demand_throughput()),
+                "ItemCount": g.item_count(),
+                "IndexSizeBytes": g.index_size_bytes(),
+            }))
+            .collect();
+    }
+    if !t.local_secondary_indexes().is_empty() {
+        v["LocalSecondaryIndexes"] = t.local_secondary_indexes().iter()
+            .map(|l| json!({
+                "IndexName": l.index_name(),
+                "KeySchema": key_schema_json(l.key_schema()),
+                "Projection": projection_json(l.projection()),
+                "ItemCount": l.item_count(),
+                "IndexSizeBytes": l.index_size_bytes(),
+            }))
+            .collect();
+    }
+    if let Some(spec) = t.stream_specification() {
+        v["StreamSpecification"] = json!({
+            "StreamEnabled": spec.stream_enabled(),
+            "StreamViewType": spec.stream_view_type().map(|s| s.as_str()),
+        });
+        v["LatestStreamArn"] = json!(t.latest_stream_arn());
+    }
+    if let Some(sse) = t.sse_description() {
+        v["SSEDescription"] = json!({
+            "Status": sse.status().map(|s| s.as_str()),
+            "SSEType": sse.sse_type().map(|s| s.as_str()),
+            "KMSMasterKeyArn": sse.kms_master_key_arn(),
+        });
+    }
+    if let Some(ttl) = &extras.ttl {
+        v["TimeToLive"] = json!({
+            "TimeToLiveStatus": ttl.time_to_live_status().map(|s| s.as_str()),
+            "AttributeName": ttl.attribute_name(),
+        });
+    }
+    if let Some(pitr) = extras.backups.as_ref().and_then(|b| b.point_in_time_recovery_description()) {
+        v["PointInTimeRecovery"] = json!({
+            "Status": pitr.point_in_time_recovery_status().map(|s| s.as_str()),
+            "EarliestRestorableDateTime": pitr.earliest_restorable_date_time().map(fmt_time),
+            "LatestRestorableDateTime": pitr.latest_restorable_date_time().map(fmt_time),
+        });
+    }
+    if let Some(tags) = &extras.tags {
+        v["Tags"] = tags.iter().map(|(k, val)| (k.clone(), Value::String(val.clone()))).collect::<Map<_, _>>().into();
+    }
+    v
+}
+
+fn print_table_text(table: &str, t: &TableDescription, extras: &TableExtras) {
+    println!("\nDynamoDB table: {}", table);
+    println!("Status: {}", t.table_status().map(|s| s.as_str()).unwrap_or("-"));
+    if let Some(arn) = t.table_arn() {
+        println!("ARN: {}", arn);
+    }
+    if let Some(created) = t.creation_date_time() {
+        println!("Created: {}", fmt_time(created));
+    }
+    println!(
+        "Items: {} (size {} bytes; refreshed about every 6 hours)",
+        t.item_count().unwrap_or(0),
+        t.table_size_bytes().unwrap_or(0)
+    );
+    let on_demand = t.billing_mode_summary().and_then(|b| b.billing_mode()) == Some(&BillingMode::PayPerRequest);
+    println!("Capacity: {}", capacity_text(on_demand, t.provisioned_throughput(), t.on_demand_throughput()));
+    if let Some(class) = t.table_class_summary().and_then(|c| c.table_class()) {
+        println!("Table class: {}", class.as_str());
+    }
+    if t.deletion_protection_enabled() == Some(true) {
+        println!("Deletion protection: enabled");
+    }
+
+    let attrs = t.attribute_definitions();
+    if !attrs.is_empty() {
+        println!("AttributeDefinitions:");
+        for a in attrs {
+            let name = a.attribute_name();
+            let typ = format!("{:?}", a.attribute_type());
+            println!("  - name: {}, type: {}", name, typ);
+        }
+    }
+    let keys = t.key_schema();
+    if !keys.is_empty() {
+        println!("KeySchema:");
+        for k in keys {
+            let name = k.attribute_name();
+            let key_type = format!("{:?}", k.key_type());
+            println!("  - name: {}, key_type: {}", name, key_type);
+        }
+    }
+    if !t.global_secondary_indexes().is_empty() {
+        println!("GlobalSecondaryIndexes:");
+        for g in t.global_secondary_indexes() {
+            println!(
+                "  - {} ({}), items: {}, size: {} bytes",
+                g.index_name().unwrap_or("-"),
+                g.index_status().map(|s| s.as_str()).unwrap_or("-"),
+                g.item_count().unwrap_or(0),
+                g.index_size_bytes().unwrap_or(0)
+            );
+            println!("      keys: {}", key_schema_text(g.key_schema()));
+            println!("      projection: {}", projection_text(g.projection()));
+            println!("      capacity: {}", capacity_text(on_demand, g.provisioned_throughput(), g.on_demand_throughput()));
+        }
+    }
+    if !t.local_secondary_indexes().is_empty() {
+        println!("LocalSecondaryIndexes:");
+        for l in t.local_secondary_indexes() {
+            println!(
+                "  - {}, items: {}, size: {} bytes",
+                l.index_name().unwrap_or("-"),
+                l.item_count().unwrap_or(0),
+                l.index_size_bytes().unwrap_or(0)
+            );
+            println!("      keys: {}", key_schema_text(l.key_schema()));
+            println!("      projection: {}", projection_text(l.projection()));
+        }
+    }
+
+    match t.stream_specification() {
+        Some(spec) if spec.stream_enabled() => println!(
+            "Stream: enabled ({}) {}",
+            spec.stream_view_type().map(|s| s.as_str()).unwrap_or("-"),
+            t.latest_stream_arn().unwrap_or_default()
+        ),
+        _ => println!("Stream: disabled"),
+    }
+    if let Some(ttl) = &extras.ttl {
+        match ttl.attribute_name() {
+            Some(attr) => println!("TTL: {} (attribute '{}')", ttl.time_to_live_status().map(|s| s.as_str()).unwrap_or("-"), attr),
+            None => println!("TTL: {}", ttl.time_to_live_status().map(|s| s.as_str()).unwrap_or("DISABLED")),
+        }
+    }
+    if let Some(pitr) = extras.backups.as_ref().and_then(|b| b.point_in_time_recovery_description()) {
+        let status = pitr.point_in_time_recovery_status().map(|s| s.as_str()).unwrap_or("-");
+        match (pitr.earliest_restorable_date_time(), pitr.latest_restorable_date_time()) {
+            (Some(from), Some(to)) => println!("PITR: {} (restorable {} .. {})", status, fmt_time(from), fmt_time(to)),
+            _ => println!("PITR: {}", status),
+        }
+    }
+    match t.sse_description() {
+        Some(sse) => println!(
+            "SSE: {} ({}){}",
+            sse.status().map(|s| s.as_str()).unwrap_or("-"),
+            sse.sse_type().map(|s| s.as_str()).unwrap_or("-"),
+            sse.kms_master_key_arn().map(|k| format!(" {}", k)).unwrap_or_default()
+        ),
+        None => println!("SSE: AWS owned key"),
+    }
+    if let Some(tags) = &extras.tags {
+        if tags.is_empty() {
+            println!("Tags: (none)");
+        } else {
+            println!("Tags:");
+            for (k, v) in tags {
+                println!("  {}={}", k, v);
+            }
+        }
+    }
+}
+
 pub async fn describe_table_schema(
     client: &DdbClient,
     table: &str,
+    json: bool,
 ) -> Result<(), aws_sdk_dynamodb::Error> {
     match client.describe_table().table_name(table).send().await {
         Ok(resp) => {
             if let Some(t) = resp.table() {
-                println!("\nDynamoDB table: {}", table);
-                let attrs = t.attribute_definitions();
-                if !attrs.is_empty() {
-                    println!("AttributeDefinitions:");
-                    for a in attrs {
-                        let name = a.attribute_name();
-                        let typ = format!("{:?}", a.attribute_type());
-                        println!("  - name: {}, type: {}", name, typ);
-                    }
-                }
-                let keys = t.key_schema();
-                if !keys.is_empty() {
-                    println!("KeySchema:");
-                    for k in keys {
-                        let name = k.attribute_name();
-                        let key_type = format!("{:?}", k.key_type());
-                        println!("  - name: {}, key_type: {}", name, key_type);
-                    }
+                let extras = describe_table_extras(client, table, t.table_arn().unwrap_or_default()).await;
+                if json {
+                    println!("{:#}", table_json(t, &extras));
+                } else {
+                    print_table_text(table, t, &extras);
                 }
             } else {
                 println!("Table {} not found or has no metadata.", table);
@@ -46,18 +344,104 @@ pub async fn describe_table_schema(
     }
 }
 
-pub async fn list_tables(client: &DdbClient) -> Result<(), aws_sdk_dynamodb::Error> {
-    let resp = client.list_tables().send().await?;
-    let names = resp.table_names();
-    if names.is_empty() {
+/// How many DescribeTable calls `list-tables --long` keeps in flight.
+const TABLE_DESCRIBE_CONCURRENCY: usize = 16;
+
+/// Every table name in the account/region (ListTables pages of up to 100),
+/// optionally restricted to names matching `pattern`.
+pub async fn list_table_names(client: &DdbClient, pattern: Option<&glob::Pattern>) -> Result<Vec<String>, aws_sdk_dynamodb::Error> {
+    let mut names = Vec::new();
+    let mut pages = client.list_tables().into_paginator().send();
+    while let Some(page) = pages.next().await {
+        names.extend(page?.table_names().iter().filter(|n| pattern.is_none_or(|p| p.matches(n))).cloned());
+    }
+    Ok(names)
+}
+
+pub async fn list_tables(
+    client: &DdbClient,
+    pattern: Option<&glob::Pattern>,
+    long: bool,
+    json: bool,
+) -> Result<usize, aws_sdk_dynamodb::Error> {
+    let names = list_table_names(client, pattern).await?;
+    if !long && !json {
+        if names.is_empty() {
+            println!("No DynamoDB tables found.");
+        } else {
+            println!("DynamoDB tables:");
+            for n in &names {
+                println!("  {}", n);
+            }
+        }
+        return Ok(names.len());
+    }
+
+    // A table can disappear between ListTables and DescribeTable; report it
+    // instead of failing the whole listing.
+    let described: Vec<(String, Result<TableDescription, String>)> = futures::stream::iter(names)
+        .map(|name| async move {
+            let resp = client.describe_table().table_name(&name).send().await;
+            let table = resp
+                .map_err(|e| DisplayErrorContext(aws_sdk_dynamodb::Error::from(e)).to_string())
+                .and_then(|r| r.table.ok_or_else(|| "no table description".to_string()));
+            (name, table)
+        })
+        .buffered(TABLE_DESCRIBE_CONCURRENCY)
+        .collect()
+        .await;
+
+    let billing = |t: &TableDescription| {
+        t.billing_mode_summary()
+            .and_then(|b| b.billing_mode())
+            .unwrap_or(&BillingMode::Provisioned)
+            .as_str()
+            .to_string()
+    };
+    if json {
+        let rows: Vec<Value> = described
+            .iter()
+            .map(|(name, t)| match t {
+                Ok(t) => json!({
+                    "TableName": name,
+                    "TableStatus": t.table_status().map(|s| s.as_str()),
+                    "ItemCount": t.item_count(),
+                    "TableSizeBytes": t.table_size_bytes(),
+                    "BillingMode": billing(t),
+                    "GlobalSecondaryIndexCount": t.global_secondary_indexes().len(),
+                    "LocalSecondaryIndexCount": t.local_secondary_indexes().len(),
+                }),
+                Err(e) => json!({ "TableName": name, "Error": e }),
+            })
+            .collect();
+        println!("{:#}", Value::Array(rows));
+        return Ok(described.len());
+    }
+
+    if described.is_empty() {
         println!("No DynamoDB tables found.");
-    } else {
-        println!("DynamoDB tables:");
-        for n in names {
-            println!("  {}", n);
+        return Ok(0);
+    }
+    println!("NAME\tSTATUS\tITEMS\tSIZE_BYTES\tBILLING\tGSIS\tLSIS");
+    for (name, t) in &described {
+        match t {
+            Ok(t) => println!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                name,
+                t.table_status().map(|s| s.as_str()).unwrap_or("-"),
+                t.item_count().unwrap_or(0),
+                t.table_size_bytes().unwrap_or(0),
+                billing(t),
+                t.global_secondary_indexes().len(),
+                t.local_secondary_indexes().len()
+            ),
+            Err(e) => {
+                println!("{}\t-\t-\t-\t-\t-\t-", name);
+                eprintln!("{}: {}", name, e);
+            }
         }
     }
-    Ok(())
+    Ok(described.len())
 }
 
 pub async fn scan_table(client: &DdbClient, table: &str) -> Result<u64, aws_sdk_dynamodb::Error> {
@@ -80,35 +464,22 @@ pub async fn scan_table(client: &DdbClient, table: &str) -> Result<u64, aws_sdk_
     Ok(count)
 }
 
-pub async fn scan_table_csv(client: &DdbClient, table: &str) -> Result<u64, aws_sdk_dynamodb::Error> {
-    let mut items: Vec<HashMap<String, AttributeValue>> = Vec::new();
-    let mut paginator = client.scan().table_name(table).into_paginator().send();
-
-    while let Some(page_res) = paginator.next().await {
-        let page = page_res?;
-        let page_items = page.items();
-        if page_items.is_empty() {
-            continue;
-        }
-        for item in page_items {
-            items.push(item.clone());
-        }
-    }
-
-    if items.is_empty() {
-        println!("(no items)");
-        return Ok(0);
-    }
-
-    // Collect all header keys in stable order
+/// Every attribute name used by any of `items`, in stable (sorted) order.
+pub fn collect_headers<'a>(items: impl IntoIterator<Item = &'a HashMap<String, AttributeValue>>) -> Vec<String> {
     let mut keys_set: HashSet<String> = HashSet::new();
-    for it in &items {
+    for it in items {
         for k in it.keys() {
             keys_set.insert(k.clone());
         }
     }
     let mut headers: Vec<String> = keys_set.into_iter().collect();
     headers.sort();
+    headers
+}
+
+/// Items as CSV: a header row of every attribute name, then one row per item.
+pub fn print_items_csv(items: &[HashMap<String, AttributeValue>]) {
+    let headers = collect_headers(items);
 
     // CSV helper to escape values
     fn escape_csv(s: &str) -> String {
@@ -122,7 +493,7 @@ pub async fn scan_table_csv(client: &DdbClient, table: &str) -> Result<u64, aws_
     println!("{}", header_line);
 
     // print rows
-    for it in &items {
+    for it in items {
         let mut row: Vec<String> = Vec::with_capacity(headers.len());
         for h in &headers {
             let val = it.get(h).map(|v| format!("{:?}", v)).unwrap_or_default();
@@ -130,11 +501,90 @@ pub async fn scan_table_csv(client: &DdbClient, table: &str) -> Result<u64, aws_
         }
         println!("{}", row.join(","));
     }
+}
 
-    Ok(items.len() as u64)
+/// Items as TSV (tabs and newlines in values escaped).
+pub fn print_items_tsv(items: &[HashMap<String, AttributeValue>]) {
+    let headers = collect_headers(items);
+
+    // TSV helper to escape values (tabs and newlines)
+    fn escape_tsv(s: &str) -> String {
+        let mut out = s.replace('\t', "\\t");
+        out = out.replace('\n', "\\n");
+        out
+    }
+
+    // print header (tab-separated)
+    let header_line = headers.join("\t");
+    println!("{}", header_line);
+
+    // print rows
+    for it in items {
+        let mut row: Vec<String> = Vec::with_capacity(headers.len());
+        for h in &headers {
+            let val = it.get(h).map(|v| format!("{:?}", v)).unwrap_or_default();
+            row.push(escape_tsv(&val));
+        }
+        println!("{}", row.join("\t"));
+    }
 }
 
-pub async fn scan_table_tsv(client: &DdbClient, table: &str) -> Result<u64, aws_sdk_dynamodb::Error> {
+/// How `sql` and similar commands print items.
+#[derive(Clone, Copy)]
+pub enum ItemFormat {
+    Table,
+    Csv,
+    Tsv,
+    Json,
+}
+
+impl std::str::FromStr for ItemFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "table" => Ok(ItemFormat::Table),
+            "csv" => Ok(ItemFormat::Csv),
+            "tsv" => Ok(ItemFormat::Tsv),
+            "json" => Ok(ItemFormat::Json),
+            _ => Err(format!("unknown output format '{}' (use table, csv, tsv or json)", s)),
+        }
+    }
+}
+
+/// Items as aligned columns of plain values (`attribute_text`).
+pub fn print_items_table(items: &[HashMap<String, AttributeValue>]) {
+    let headers = collect_headers(items);
+    let rows: Vec<Vec<String>> = items
+        .iter()
+        .map(|it| headers.iter().map(|h| it.get(h).map(attribute_text).unwrap_or_default().replace('\n', "\\n")).collect())
+        .collect();
+    let widths: Vec<usize> = headers
+        .iter()
+        .enumerate()
+        .map(|(i, h)| rows.iter().map(|r| r[i].chars().count()).chain([h.chars().count()]).max().unwrap_or(0))
+        .collect();
+    let line = |cells: &[String]| {
+        let padded: Vec<String> = cells.iter().zip(&widths).map(|(c, w)| format!("{:<w$}", c, w = *w)).collect();
+        println!("{}", padded.join("  ").trim_end());
+    };
+    line(&headers);
+    line(&widths.iter().map(|w| "-".repeat(*w)).collect::<Vec<_>>());
+    for r in &rows {
+        line(r);
+    }
+}
+
+pub fn print_items(items: &[HashMap<String, AttributeValue>], format: ItemFormat) {
+    match format {
+        ItemFormat::Table => print_items_table(items),
+        ItemFormat::Csv => print_items_csv(items),
+        ItemFormat::Tsv => print_items_tsv(items),
+        ItemFormat::Json => println!("{:#}", Value::Array(items.iter().map(item_to_json).collect())),
+    }
+}
+
+pub async fn scan_table_csv(client: &DdbClient, table: &str) -> Result<u64, aws_sdk_dynamodb::Error> {
     let mut items: Vec<HashMap<String, AttributeValue>> = Vec::new();
     let mut paginator = client.scan().table_name(table).into_paginator().send();
 
@@ -154,37 +604,31 @@ pub async fn scan_table_tsv(client: &DdbClient, table: &str) -> Result<u64, aws_
         return Ok(0);
     }
 
-    // Collect all header keys in stable order
-    let mut keys_set: HashSet<String> = HashSet::new();
-    for it in &items {
-        for k in it.keys() {
-            keys_set.insert(k.clone());
-        }
-    }
-    let mut headers: Vec<String> = keys_set.into_iter().collect();
-    headers.sort();
-
-    // TSV helper to escape values (tabs and newlines)
-    fn escape_tsv(s: &str) -> String {
-        let mut out = s.replace('\t', "\\t");
-        out = out.replace('\n', "\\n");
-        out
-    }
+    print_items_csv(&items);
+    Ok(items.len() as u64)
+}
 
-    // print header (tab-separated)
-    let header_line = headers.join("\t");
-    println!("{}", header_line);
+pub async fn scan_table_tsv(client: &DdbClient, table: &str) -> Result<u64, aws_sdk_dynamodb::Error> {
+    let mut items: Vec<HashMap<String, AttributeValue>> = Vec::new();
+    let mut paginator = client.scan().table_name(table).into_paginator().send();
 
-    // print rows
-    for it in &items {
-        let mut row: Vec<String> = Vec::with_capacity(headers.len());
-        for h in &headers {
-            let val = it.get(h).map(|v| format!("{:?}", v)).unwrap_or_default();
-            row.push(escape_tsv(&val));
+    while let Some(page_res) = paginator.next().await {
+        let page = page_res?;
+        let page_items = page.items();
+        if page_items.is_empty() {
+            continue;
+        }
+        for item in page_items {
+            items.push(item.clone());
         }
-        println!("{}", row.join("\t"));
     }
 
+    if items.is_empty() {
+        println!("(no items)");
+        return Ok(0);
+    }
+
+    print_items_tsv(&items);
     Ok(items.len() as u64)
 }
 
@@ -211,7 +655,7 @@ pub async fn delete_all_items(client: &DdbClient, table: &str) -> Result<u64, aw
     let mut paginator = client
         .scan()
         .table_name(table)
-        .projection_expression(&key_attrs.join(","))
+        .projection_expression(key_attrs.join(","))
         .into_paginator()
         .send();
 
@@ -375,3 +819,137 @@ pub async fn scan_projected_attributes(
 
     Ok(items)
 }
+
+/// The DynamoDB type descriptor of a value (`S`, `N`, `BOOL`, `SS`, ...).
+pub fn attribute_type_name(v: &AttributeValue) -> &'static str {
+    match v {
+        AttributeValue::S(_) => "S",
+        AttributeValue::N(_) => "N",
+        AttributeValue::B(_) => "B",
+        AttributeValue::Bool(_) => "BOOL",
+        AttributeValue::Null(_) => "NULL",
+        AttributeValue::Ss(_) => "SS",
+        AttributeValue::Ns(_) => "NS",
+        AttributeValue::Bs(_) => "BS",
+        AttributeValue::L(_) => "L",
+        AttributeValue::M(_) => "M",
+        _ => "unknown type",
+    }
+}
+
+/// Whether `s` is a number DynamoDB accepts: Rust also parses `inf`, `NaN`
+/// and `infinity`, which DynamoDB rejects.
+pub fn is_number(s: &str) -> bool {
+    s.parse::<f64>().is_ok_and(|n| n.is_finite())
+}
+
+/// Parse a command-line value: `true`/`false` -> BOOL, numbers -> N, anything else -> S.
+pub fn infer_attribute_value(val: &str) -> AttributeValue {
+    if val.eq_ignore_ascii_case("true") || val.eq_ignore_ascii_case("false") {
+        AttributeValue::Bool(val.eq_ignore_ascii_case("true"))
+    } else if is_number(val) {
+        AttributeValue::N(val.to_string())
+    } else {
+        AttributeValue::S(val.to_string())
+    }
+}
+
+/// Parse a value with an optional explicit type prefix: `S:123`, `N:42`,
+/// `BOOL:true` or `NULL:`; without a prefix the type is inferred.
+pub fn parse_typed_value(val: &str) -> Result<AttributeValue, String> {
+    match val.split_once(':') {
+        Some(("S", s)) => Ok(AttributeValue::S(s.to_string())),
+        Some(("N", n)) if is_number(n.trim()) => Ok(AttributeValue::N(n.trim().to_string())),
+        Some(("N", n)) => Err(format!("'{}' is not a number", n)),
+        Some(("BOOL", b)) => match b.to_ascii_lowercase().as_str() {
+            "true" => Ok(AttributeValue::Bool(true)),
+            "false" => Ok(AttributeValue::Bool(false)),
+            _ => Err(format!("'{}' is not true or false", b)),
+        },
+        Some(("NULL", _)) => Ok(AttributeValue::Null(true)),
+        _ => Ok(infer_attribute_value(val)),
+    }
+}
+
+/// Plain text for a value: strings and numbers as-is, booleans as
+/// `true`/`false`, anything else as compact DynamoDB JSON.
+pub fn attribute_text(v: &AttributeValue) -> String {
+    match v {
+        AttributeValue::S(s) => s.clone(),
+        AttributeValue::N(n) => n.clone(),
+        AttributeValue::Bool(b) => b.to_string(),
+        other => attr_to_json(other).to_string(),
+    }
+}
+
+/// Number formatting without a trailing `.0` for integers.
+pub fn number_text(x: f64) -> String {
+    if x.fract() == 0.0 && x.abs() < 1e15 { format!("{}", x as i64) } else { x.to_string() }
+}
+
+/// An attribute value in DynamoDB JSON (`{"S": "x"}`, `{"N": "1"}`, ...), the
+/// form used by the AWS CLI; binary values are base64.
+pub fn attr_to_json(v: &AttributeValue) -> Value {
+    use base64::Engine;
+    let b64 = |b: &aws_sdk_dynamodb::primitives::Blob| Value::from(base64::engine::general_purpose::STANDARD.encode(b.as_ref()));
+    match v {
+        AttributeValue::S(s) => json!({ "S": s }),
+        AttributeValue::N(n) => json!({ "N": n }),
+        AttributeValue::Bool(b) => json!({ "BOOL": b }),
+        AttributeValue::Null(_) => json!({ "NULL": true }),
+        AttributeValue::B(b) => json!({ "B": b64(b) }),
+        AttributeValue::Ss(ss) => json!({ "SS": ss }),
+        AttributeValue::Ns(ns) => json!({ "NS": ns }),
+        AttributeValue::Bs(bs) => json!({ "BS": bs.iter().map(b64).collect::<Vec<_>>() }),
+        AttributeValue::L(l) => json!({ "L": l.iter().map(attr_to_json).collect::<Vec<_>>() }),
+        AttributeValue::M(m) => json!({ "M": item_to_json(m) }),
+        _ => Value::Null,
+    }
+}
+
+pub fn attr_from_json(v: &Value) -> Result<AttributeValue, String> {
+    use base64::Engine;
+    let obj = v.as_object().filter(|o| o.len() == 1).ok_or_else(|| format!("expected a single-key DynamoDB JSON value, got {}", v))?;
+    let (tag, inner) = obj.iter().next().unwrap();
+    let string = |x: &Value| x.as_str().map(|s| s.to_string()).ok_or_else(|| format!("{} value must be a string: {}", tag, x));
+    let strings = |x: &Value| -> Result<Vec<String>, String> {
+        x.as_array().ok_or_else(|| format!("{} value must be a list: {}", tag, x))?.iter().map(string).collect()
+    };
+    let blob = |s: String| {
+        base64::engine::general_purpose::STANDARD
+            .decode(s)
+            .map(aws_sdk_dynamodb::primitives::Blob::new)
+            .map_err(|e| format!("invalid base64 in {} value: {}", tag, e))
+    };
+    Ok(match tag.as_str() {
+        "S" => AttributeValue::S(string(inner)?),
+        // Accept bare JSON numbers as well as the canonical string form.
+        "N" => AttributeValue::N(if inner.is_number() { inner.to_string() } else { string(inner)? }),
+        "BOOL" => AttributeValue::Bool(inner.as_bool().ok_or_else(|| format!("BOOL value must be true/false: {}", inner))?),
+        "NULL" => AttributeValue::Null(true),
+        "B" => AttributeValue::B(blob(string(inner)?)?),
+        "SS" => AttributeValue::Ss(strings(inner)?),
+        "NS" => AttributeValue::Ns(strings(inner)?),
+        "BS" => AttributeValue::Bs(strings(inner)?.into_iter().map(blob).collect::<Result<_, _>>()?),
+        "L" => AttributeValue::L(
+            inner.as_array().ok_or_else(|| format!("L value must be a list: {}", inner))?.iter().map(attr_from_json).collect::<Result<_, _>>()?,
+        ),
+        "M" => AttributeValue::M(item_from_json(inner)?),
+        other => return Err(format!("unknown DynamoDB type '{}'", other)),
+    })
+}
+
+/// An item (or key) as a DynamoDB JSON object, attributes sorted by name.
+pub fn item_to_json(item: &HashMap<String, AttributeValue>) -> Value {
+    let mut names: Vec<&String> = item.keys().collect();
+    names.sort();
+    Value::Object(names.into_iter().map(|k| (k.clone(), attr_to_json(&item[k]))).collect())
+}
+
+pub fn item_from_json(v: &Value) -> Result<HashMap<String, AttributeValue>, String> {
+    v.as_object()
+        .ok_or_else(|| format!("expected a DynamoDB JSON object, got {}", v))?
+        .iter()
+        .map(|(k, a)| Ok((k.clone(), attr_from_json(a)?)))
+        .collect()
+}
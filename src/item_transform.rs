@@ -0,0 +1,282 @@
+// Per-item attribute rewrites shared by `copy-table` and `migrate`.
+//
+// Rules come from repeatable command-line options and are applied to each
+// item in the order given:
+//   --rename OLD=NEW     move an attribute
+//   --drop ATTR          remove an attribute
+//   --set ATTR=VALUE     set an attribute (value typed like `set-attr`, or
+//                        forced with a prefix: S:123, N:5, BOOL:true, NULL:)
+//   --default ATTR=VALUE set an attribute only where it is missing
+//   --convert ATTR=TYPE  change an attribute's type (S, N, BOOL, SS or NS)
+
+use crate::dynamodb::{attribute_type_name, is_number, parse_typed_value};
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+type Item = HashMap<String, AttributeValue>;
+
+/// The options `Transform::parse` understands, for `cli::take_opts_ordered`.
+pub const FLAGS: &[&str] = &["--rename", "--drop", "--set", "--default", "--convert"];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TargetType {
+    S,
+    N,
+    Bool,
+    Ss,
+    Ns,
+}
+
+impl std::str::FromStr for TargetType {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_uppercase().as_str() {
+            "S" => Ok(TargetType::S),
+            "N" => Ok(TargetType::N),
+            "BOOL" => Ok(TargetType::Bool),
+            "SS" => Ok(TargetType::Ss),
+            "NS" => Ok(TargetType::Ns),
+            _ => Err(format!("unsupported target type '{}' (use S, N, BOOL, SS or NS)", s)),
+        }
+    }
+}
+
+pub enum Transform {
+    Rename(String, String),
+    Drop(String),
+    Set(String, AttributeValue),
+    Default(String, AttributeValue),
+    Convert(String, TargetType),
+}
+
+impl Transform {
+    /// Parse one of `FLAGS` and its argument.
+    pub fn parse(flag: &str, spec: &str) -> Result<Self, String> {
+        let pair = || spec.split_once('=').ok_or_else(|| format!("{} expects NAME=VALUE, got '{}'", flag, spec));
+        match flag {
+            "--rename" => pair().map(|(a, b)| Transform::Rename(a.to_string(), b.to_string())),
+            "--drop" => Ok(Transform::Drop(spec.to_string())),
+            "--set" => {
+                let (a, v) = pair()?;
+                Ok(Transform::Set(a.to_string(), parse_typed_value(v).map_err(|e| format!("--set {}: {}", a, e))?))
+            }
+            "--default" => {
+                let (a, v) = pair()?;
+                Ok(Transform::Default(a.to_string(), parse_typed_value(v).map_err(|e| format!("--default {}: {}", a, e))?))
+            }
+            "--convert" => {
+                let (a, t) = pair()?;
+                Ok(Transform::Convert(a.to_string(), t.parse()?))
+            }
+            _ => Err(format!("unknown transform {}", flag)),
+        }
+    }
+
+    /// The attributes this rule reads or writes.
+    pub fn attributes(&self) -> Vec<&str> {
+        match self {
+            Transform::Rename(a, b) => vec![a, b],
+            Transform::Drop(a) | Transform::Set(a, _) | Transform::Default(a, _) | Transform::Convert(a, _) => vec![a],
+        }
+    }
+
+    /// Apply the rule to `item`; fails only when a conversion isn't possible.
+    pub fn apply(&self, item: &mut Item) -> Result<(), String> {
+        match self {
+            Transform::Rename(from, to) => {
+                if let Some(v) = item.remove(from) {
+                    item.insert(to.clone(), v);
+                }
+            }
+            Transform::Drop(name) => {
+                item.remove(name);
+            }
+            Transform::Set(name, value) => {
+                item.insert(name.clone(), value.clone());
+            }
+            Transform::Default(name, value) => {
+                item.entry(name.clone()).or_insert_with(|| value.clone());
+            }
+            Transform::Convert(name, to) => {
+                if let Some(v) = item.get(name) {
+                    let converted = convert(v, *to).map_err(|e| format!("{}: {}", name, e))?;
+                    item.insert(name.clone(), converted);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_bool(s: &str) -> Option<bool> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "y" => Some(true),
+        "false" | "0" | "no" | "n" | "" => Some(false),
+        _ => None,
+    }
+}
+
+fn parse_number(s: &str) -> Result<String, String> {
+    let t = s.trim();
+    if is_number(t) { Ok(t.to_string()) } else { Err(format!("'{}' is not a number", s)) }
+}
+
+/// Comma-separated list items; DynamoDB sets can't be empty.
+fn split_set(s: &str) -> Result<Vec<String>, String> {
+    let mut parts: Vec<String> = s.split(',').map(|p| p.trim().to_string()).filter(|p| !p.is_empty()).collect();
+    parts.sort();
+    parts.dedup();
+    if parts.is_empty() { Err(format!("'{}' would be an empty set", s)) } else { Ok(parts) }
+}
+
+fn convert(v: &AttributeValue, to: TargetType) -> Result<AttributeValue, String> {
+    use AttributeValue as A;
+    Ok(match (v, to) {
+        (A::S(_), TargetType::S) | (A::N(_), TargetType::N) | (A::Bool(_), TargetType::Bool) => v.clone(),
+        (A::Ss(_), TargetType::Ss) | (A::Ns(_), TargetType::Ns) => v.clone(),
+
+        (A::N(n), TargetType::Bool) => A::Bool(n.parse::<f64>().map_err(|_| format!("'{}' is not a number", n))? != 0.0),
+        (A::S(s), TargetType::Bool) => A::Bool(parse_bool(s).ok_or_else(|| format!("'{}' is not a boolean", s))?),
+        (A::S(s), TargetType::N) => A::N(parse_number(s)?),
+        (A::Bool(b), TargetType::N) => A::N(if *b { "1" } else { "0" }.to_string()),
+        (A::N(n), TargetType::S) => A::S(n.clone()),
+        (A::Bool(b), TargetType::S) => A::S(b.to_string()),
+        (A::Ss(ss), TargetType::S) | (A::Ns(ss), TargetType::S) => A::S(ss.join(",")),
+
+        (A::S(s), TargetType::Ss) => A::Ss(split_set(s)?),
+        (A::N(n), TargetType::Ss) => A::Ss(vec![n.clone()]),
+        (A::Ns(ns), TargetType::Ss) => A::Ss(ns.clone()),
+        (A::S(s), TargetType::Ns) => A::Ns(split_set(s)?.iter().map(|p| parse_number(p)).collect::<Result<_, _>>()?),
+        (A::N(n), TargetType::Ns) => A::Ns(vec![n.clone()]),
+        (A::Ss(ss), TargetType::Ns) => A::Ns(ss.iter().map(|p| parse_number(p)).collect::<Result<_, _>>()?),
+        (A::L(l), TargetType::Ss | TargetType::Ns) => {
+            let mut set = l
+                .iter()
+                .map(|e| match e {
+                    A::S(s) | A::N(s) => Ok(s.clone()),
+                    _ => Err("list elements must be strings or numbers".to_string()),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            set.sort();
+            set.dedup();
+            if set.is_empty() {
+                return Err("an empty list would be an empty set".to_string());
+            }
+            match to {
+                TargetType::Ss => A::Ss(set),
+                _ => A::Ns(set.iter().map(|p| parse_number(p)).collect::<Result<_, _>>()?),
+            }
+        }
+        _ => return Err(format!("cannot convert {} to {}", attribute_type_name(v), target_name(to))),
+    })
+}
+
+fn target_name(t: TargetType) -> &'static str {
+    match t {
+        TargetType::S => "S",
+        TargetType::N => "N",
+        TargetType::Bool => "BOOL",
+        TargetType::Ss => "SS",
+        TargetType::Ns => "NS",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AttributeValue as A;
+
+    fn s(v: &str) -> AttributeValue {
+        A::S(v.to_string())
+    }
+
+    fn n(v: &str) -> AttributeValue {
+        A::N(v.to_string())
+    }
+
+    fn strings(v: &[&str]) -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn convert_same_type_is_unchanged() {
+        assert_eq!(convert(&s("x"), TargetType::S).unwrap(), s("x"));
+        assert_eq!(convert(&n("1.5"), TargetType::N).unwrap(), n("1.5"));
+        assert_eq!(convert(&A::Bool(true), TargetType::Bool).unwrap(), A::Bool(true));
+        assert_eq!(convert(&A::Ss(strings(&["a"])), TargetType::Ss).unwrap(), A::Ss(strings(&["a"])));
+        assert_eq!(convert(&A::Ns(strings(&["1"])), TargetType::Ns).unwrap(), A::Ns(strings(&["1"])));
+    }
+
+    #[test]
+    fn convert_to_bool() {
+        assert_eq!(convert(&n("0"), TargetType::Bool).unwrap(), A::Bool(false));
+        assert_eq!(convert(&n("-2.5"), TargetType::Bool).unwrap(), A::Bool(true));
+        assert_eq!(convert(&s(" Yes "), TargetType::Bool).unwrap(), A::Bool(true));
+        assert_eq!(convert(&s("0"), TargetType::Bool).unwrap(), A::Bool(false));
+        assert_eq!(convert(&s(""), TargetType::Bool).unwrap(), A::Bool(false));
+        assert!(convert(&s("maybe"), TargetType::Bool).is_err());
+    }
+
+    #[test]
+    fn convert_to_number() {
+        assert_eq!(convert(&s(" 42 "), TargetType::N).unwrap(), n("42"));
+        assert_eq!(convert(&A::Bool(true), TargetType::N).unwrap(), n("1"));
+        assert_eq!(convert(&A::Bool(false), TargetType::N).unwrap(), n("0"));
+        assert!(convert(&s("abc"), TargetType::N).is_err());
+        assert!(convert(&s("NaN"), TargetType::N).is_err());
+        assert!(convert(&s("inf"), TargetType::N).is_err());
+    }
+
+    #[test]
+    fn convert_to_string() {
+        assert_eq!(convert(&n("7"), TargetType::S).unwrap(), s("7"));
+        assert_eq!(convert(&A::Bool(false), TargetType::S).unwrap(), s("false"));
+        assert_eq!(convert(&A::Ss(strings(&["a", "b"])), TargetType::S).unwrap(), s("a,b"));
+        assert_eq!(convert(&A::Ns(strings(&["1", "2"])), TargetType::S).unwrap(), s("1,2"));
+    }
+
+    #[test]
+    fn convert_to_sets() {
+        assert_eq!(convert(&s("b, a,,b"), TargetType::Ss).unwrap(), A::Ss(strings(&["a", "b"])));
+        assert_eq!(convert(&n("3"), TargetType::Ss).unwrap(), A::Ss(strings(&["3"])));
+        assert_eq!(convert(&A::Ns(strings(&["1", "2"])), TargetType::Ss).unwrap(), A::Ss(strings(&["1", "2"])));
+        assert_eq!(convert(&s("2,1"), TargetType::Ns).unwrap(), A::Ns(strings(&["1", "2"])));
+        assert_eq!(convert(&n("3"), TargetType::Ns).unwrap(), A::Ns(strings(&["3"])));
+        assert_eq!(convert(&A::Ss(strings(&["1", "2"])), TargetType::Ns).unwrap(), A::Ns(strings(&["1", "2"])));
+        assert!(convert(&s(" , "), TargetType::Ss).is_err());
+        assert!(convert(&s("1,x"), TargetType::Ns).is_err());
+        assert!(convert(&A::Ss(strings(&["x"])), TargetType::Ns).is_err());
+    }
+
+    #[test]
+    fn convert_lists_to_sets() {
+        let list = A::L(vec![s("b"), n("1"), s("b")]);
+        assert_eq!(convert(&list, TargetType::Ss).unwrap(), A::Ss(strings(&["1", "b"])));
+        assert!(convert(&list, TargetType::Ns).is_err());
+        assert_eq!(convert(&A::L(vec![n("2"), n("1")]), TargetType::Ns).unwrap(), A::Ns(strings(&["1", "2"])));
+        assert!(convert(&A::L(vec![]), TargetType::Ss).is_err());
+        assert!(convert(&A::L(vec![A::Bool(true)]), TargetType::Ss).is_err());
+    }
+
+    #[test]
+    fn convert_rejects_other_types() {
+        assert!(convert(&A::Null(true), TargetType::S).is_err());
+        assert!(convert(&A::M(HashMap::new()), TargetType::N).is_err());
+        assert!(convert(&A::Ss(strings(&["a"])), TargetType::Bool).is_err());
+        assert!(convert(&A::L(vec![s("a")]), TargetType::S).is_err());
+    }
+
+    #[test]
+    fn set_and_default_values_are_typed() {
+        let Transform::Set(_, v) = Transform::parse("--set", "a=S:123").unwrap() else { panic!() };
+        assert_eq!(v, s("123"));
+        let Transform::Set(_, v) = Transform::parse("--set", "a=123").unwrap() else { panic!() };
+        assert_eq!(v, n("123"));
+        let Transform::Default(_, v) = Transform::parse("--default", "a=NULL:").unwrap() else { panic!() };
+        assert_eq!(v, A::Null(true));
+        assert!(Transform::parse("--set", "a=N:abc").is_err());
+        assert!(Transform::parse("--default", "a=BOOL:maybe").is_err());
+        assert!(Transform::parse("--set", "a").is_err());
+    }
+}
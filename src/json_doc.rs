@@ -0,0 +1,84 @@
+// Loading and reading the JSON/YAML documents that commands take as input
+// (bucket configurations, table schemas, statement and transaction files).
+//
+// Field readers treat a missing or null field as absent and reject a field of
+// the wrong type, so a typo in a document is reported instead of ignored.
+
+use serde_json::{Map, Value};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Read a JSON or YAML (by `.yaml`/`.yml` extension) document from `path`.
+pub fn load_document(path: &str) -> Result<Value, BoxError> {
+    let text = std::fs::read_to_string(path)?;
+    if path.ends_with(".yaml") || path.ends_with(".yml") {
+        Ok(serde_yaml::from_str(&text)?)
+    } else {
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+pub fn field<'a>(v: &'a Value, name: &str) -> Option<&'a Value> {
+    v.get(name).filter(|f| !f.is_null())
+}
+
+fn type_error(name: &str, expected: &str) -> BoxError {
+    format!("{} must be {}", name, expected).into()
+}
+
+pub fn str_field(v: &Value, name: &str) -> Result<Option<String>, BoxError> {
+    field(v, name).map(|f| f.as_str().map(|s| s.to_string()).ok_or_else(|| type_error(name, "a string"))).transpose()
+}
+
+pub fn i32_field(v: &Value, name: &str) -> Result<Option<i32>, BoxError> {
+    i64_field(v, name)?.map(|n| i32::try_from(n).map_err(|_| type_error(name, "a 32-bit integer"))).transpose()
+}
+
+pub fn i64_field(v: &Value, name: &str) -> Result<Option<i64>, BoxError> {
+    field(v, name).map(|f| f.as_i64().ok_or_else(|| type_error(name, "an integer"))).transpose()
+}
+
+pub fn bool_field(v: &Value, name: &str) -> Result<Option<bool>, BoxError> {
+    field(v, name).map(|f| f.as_bool().ok_or_else(|| type_error(name, "true or false"))).transpose()
+}
+
+/// A list field; missing means empty.
+pub fn list_field<'a>(v: &'a Value, name: &str) -> Result<&'a [Value], BoxError> {
+    match field(v, name) {
+        Some(f) => f.as_array().map(|a| a.as_slice()).ok_or_else(|| type_error(name, "a list")),
+        None => Ok(&[]),
+    }
+}
+
+/// A list of strings; missing or empty gives `None`.
+pub fn strings_field(v: &Value, name: &str) -> Result<Option<Vec<String>>, BoxError> {
+    let list = list_field(v, name)?;
+    if list.is_empty() {
+        return Ok(None);
+    }
+    list.iter()
+        .map(|s| s.as_str().map(|s| s.to_string()).ok_or_else(|| type_error(name, "a list of strings")))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// A `{name: string}` map, such as `ExpressionAttributeNames`.
+pub fn string_map_field(v: &Value, name: &str) -> Result<Option<std::collections::HashMap<String, String>>, BoxError> {
+    let Some(f) = field(v, name) else { return Ok(None) };
+    let map = f.as_object().ok_or_else(|| type_error(name, "an object of strings"))?;
+    map.iter()
+        .map(|(k, v)| v.as_str().map(|s| (k.clone(), s.to_string())).ok_or_else(|| type_error(name, "an object of strings")))
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Build a JSON object from `(name, value)` pairs, skipping nulls and empty
+/// lists so documents only show what is actually set.
+pub fn object(fields: Vec<(&str, Value)>) -> Value {
+    let map: Map<String, Value> = fields
+        .into_iter()
+        .filter(|(_, v)| !v.is_null() && v.as_array().is_none_or(|a| !a.is_empty()))
+        .map(|(k, v)| (k.to_string(), v))
+        .collect();
+    Value::Object(map)
+}
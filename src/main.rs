@@ -1,8 +1,22 @@
 use aws_sdk_s3::Client as S3Client;
 use aws_sdk_dynamodb::Client as DdbClient;
 
+mod archive;
+mod bucket_config;
+mod cli;
+mod diff;
 mod s3;
+mod sql;
 mod dynamodb;
+mod item_transform;
+mod json_doc;
+mod migrate;
+mod partiql;
+mod table_aggregate;
+mod table_copy;
+mod table_profile;
+mod table_schema;
+mod transact;
 
 use aws_sdk_dynamodb::types::AttributeValue;
 use std::collections::HashMap;
@@ -13,13 +27,52 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let cmd = args.next().expect(
                 "Usage: rustawssdk <command> [...]
                 Commands:
-                    list-buckets
-                    list-s3 <bucket>
-                    describe-table <table>
+                    list-buckets [--long] [--json]
+                    list-s3 <bucket> [prefix] [--match GLOB] [--regex RE] [--larger-than SIZE] [--modified-since TIME]
+                    s3 stat <bucket> <key> [--json]
+                    s3 presign <bucket> <key|prefix> [--method get|put] [--expires 1h] [--content-type T] [--recursive --output FILE]
+                    s3 rm <bucket> <key|prefix> [--recursive] [--dry-run] [--include GLOB] [--exclude GLOB]
+                    s3 cp <src-bucket> <src-key|prefix> <dst-bucket> <dst-key|prefix> [--recursive] [--dry-run] [--include GLOB] [--exclude GLOB] [--keys-from FILE|-]
+                    s3 mv <src-bucket> <src-key|prefix> <dst-bucket> <dst-key|prefix> [--recursive] [--dry-run] [--include GLOB] [--exclude GLOB] [--keys-from FILE|-]
+                    s3 versions <bucket> [prefix] [--json]
+                    s3 restore-version <bucket> <key> <version-id>
+                    s3 undelete <bucket> <key>
+                    s3 purge-versions <bucket> [prefix] --older-than 30d [--dry-run]
+                    s3 tag get <bucket> <key> [--json]
+                    s3 tag set <bucket> <key|prefix> <k=v> [k=v ...] [--replace] [--recursive [filters]] [--dry-run]
+                    s3 tag delete <bucket> <key|prefix> [k ...] [--recursive [filters]] [--dry-run]
+                    s3 restore <bucket> <key|prefix> [--days 7] [--tier bulk|standard|expedited] [--recursive [filters]] [--dry-run]
+                    s3 restore-status <bucket> <key|prefix> [--recursive [filters]] [--json] [--watch 15m]
+                    s3 lifecycle|cors|policy|encryption|public-access-block get <bucket>
+                    s3 lifecycle|cors|policy|encryption|public-access-block put <bucket> <file.json|file.yaml> [--dry-run] [--yes]
+                    s3 lifecycle|cors|policy|encryption|public-access-block delete <bucket> [--yes]
+                    s3 multipart list <bucket> [prefix] [--json]
+                    s3 multipart abort <bucket> [prefix] --older-than 7d [--dry-run]
+                    s3 grep <pattern> <bucket> [prefix] [-i] [--max-bytes 10MB] [filters]
+                    s3 diff <bucket-a>[/prefix] <bucket-b>[/prefix] [--size-only] [--keys]
+                    s3 select <bucket> <key> \"<SQL>\" [--format csv|tsv|json|parquet] [--local]
+                    s3 archive <bucket> <prefix> --to <file|s3://bucket/key|-> [--format tar|tar.gz|tar.zst|zip] [filters]
+                    s3 unarchive <file|s3://bucket/key|-> <bucket> [prefix] [--format tar|tar.gz|tar.zst|zip] [--dry-run]
+                    s3 mb <bucket> [--region R]
+                    s3 rb <bucket> [--force] [--yes]
+                    describe-table <table> [--json] [--schema]
+                    create-table --from <schema.yaml|schema.json> [--name NAME] [--no-wait] [--wait-timeout 30m]
+                    table-diff <table> <schema-file|other-table> [--apply] [--dry-run] [--yes] [--wait-timeout 30m]
+                    migrate <table> [--rename OLD=NEW] [--drop ATTR] [--set ATTR=[TYPE:]VALUE] [--default ATTR=[TYPE:]VALUE] [--convert ATTR=TYPE]
+                        [--segments 4] [--dry-run]
+                    profile-table <table> [--sample N] [--json]
+                    count <table> [--where COND ...] [--segments 4]
+                    aggregate <table> [attribute] [--group-by ATTR] [--where COND ...] [--segments 4] [--json]
+                    sql \"<PartiQL>\" [--param VALUE ...] [--consistent] [--format table|csv|tsv|json]
+                    sql --file statements.yaml [--format table|csv|tsv|json]
+                    transact <file.json|file.yaml> [--token TOKEN]
+                    transact-get <file.json|file.yaml> [--format table|csv|tsv|json]
                     scan-table <table>         # print all items in the table (paginated)
                     scan-table-csv <table>     # print all items as CSV (headers inferred)
                     scan-table-tsv <table>     # print all items as TSV (headers inferred)
-                    list-tables
+                    list-tables [--match GLOB] [--long] [--json]
+                    copy-table <src> <dst> [--segments 4] [--create] [--dst-profile P] [--dst-region R] [--dst-endpoint URL]
+                        [--rename OLD=NEW] [--drop ATTR] [--set ATTR=[TYPE:]VALUE] [--default ATTR=[TYPE:]VALUE] [--convert ATTR=TYPE] [--checkpoint FILE]
                     delete-all <table>
                     item-exists <table> <key1=value1> [key2=value2 ...]
                     set-attr <table> <attribute> <value> <key1=value1> [key2=value2 ...]
@@ -32,17 +85,121 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     match cmd.as_str() {
         "list-buckets" => {
-            let count = s3::list_s3_buckets(&s3_client).await?;
-            println!("\nTotal: {} bucket(s)", count);
+            let mut rest: Vec<String> = args.collect();
+            let long = cli::take_flag(&mut rest, "--long");
+            let json = cli::take_flag(&mut rest, "--json");
+            if long || json {
+                let count = s3::list_s3_buckets_long(&s3_client, json).await?;
+                if !json {
+                    println!("\nTotal: {} bucket(s)", count);
+                }
+            } else {
+                let count = s3::list_s3_buckets(&s3_client).await?;
+                println!("\nTotal: {} bucket(s)", count);
+            }
         }
         "list-s3" => {
-            let bucket = args.next().expect("Usage: list-s3 <bucket>");
-            let count = s3::list_s3_objects(&s3_client, &bucket).await?;
+            let mut rest: Vec<String> = args.collect();
+            let filter = take_list_filter(&mut rest)?;
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect("Usage: list-s3 <bucket> [prefix] [--match GLOB] [--regex RE] [--larger-than SIZE] [--modified-since TIME]");
+            let prefix = args.next().unwrap_or_default();
+            let count = s3::list_s3_objects(&s3_client, &bucket, &prefix, &filter).await?;
             println!("\nTotal: {} object(s)", count);
         }
+        "s3" => {
+            let sub = args.next().expect("Usage: s3 <subcommand> [...]");
+            run_s3(&s3_client, &sub, args.collect()).await?;
+        }
         "describe-table" => {
-            let table = args.next().expect("Usage: describe-table <table>");
-            dynamodb::describe_table_schema(&ddb_client, &table).await?;
+            let mut rest: Vec<String> = args.collect();
+            let json = cli::take_flag(&mut rest, "--json");
+            let schema = cli::take_flag(&mut rest, "--schema");
+            let mut args = rest.into_iter();
+            let table = args.next().expect("Usage: describe-table <table> [--json] [--schema]");
+            if schema {
+                let schema = table_schema::schema_from_table(&ddb_client, &table).await?;
+                print!("{}", table_schema::render(&schema.to_json(), json)?);
+                if json {
+                    println!();
+                }
+            } else {
+                dynamodb::describe_table_schema(&ddb_client, &table, json).await?;
+            }
+        }
+        "create-table" => {
+            let mut rest: Vec<String> = args.collect();
+            let usage = "Usage: create-table --from <schema.yaml|schema.json> [--name NAME] [--no-wait] [--wait-timeout 30m]";
+            let path = cli::take_opt(&mut rest, "--from").expect(usage);
+            let name = cli::take_opt(&mut rest, "--name");
+            let no_wait = cli::take_flag(&mut rest, "--no-wait");
+            let timeout = cli::take_opt(&mut rest, "--wait-timeout").map(|d| cli::parse_duration(&d)).transpose()?;
+            let doc = json_doc::load_document(&path)?;
+            let mut schema = table_schema::TableSchema::from_json(&doc)?;
+            if let Some(name) = name {
+                schema.table_name = name;
+            }
+            if no_wait && let Some(attr) = &schema.ttl_attribute {
+                return Err(format!(
+                    "--no-wait can't be used when the schema sets TimeToLive ({}): TTL can only be enabled once the table is ACTIVE",
+                    attr
+                )
+                .into());
+            }
+            println!("Creating table {}...", schema.table_name);
+            let wait = (!no_wait).then(|| timeout.unwrap_or(table_schema::DEFAULT_WAIT_TIMEOUT));
+            table_schema::create_table(&ddb_client, &schema, wait).await?;
+            if no_wait {
+                println!("CreateTable accepted for {}", schema.table_name);
+            } else {
+                println!("Table {} is ACTIVE", schema.table_name);
+            }
+        }
+        "table-diff" => {
+            let mut rest: Vec<String> = args.collect();
+            let usage = "Usage: table-diff <table> <schema-file|other-table> [--apply] [--dry-run] [--yes] [--wait-timeout 30m]";
+            let apply = cli::take_flag(&mut rest, "--apply");
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let yes = cli::take_flag(&mut rest, "--yes");
+            let timeout = cli::take_opt(&mut rest, "--wait-timeout").map(|d| cli::parse_duration(&d)).transpose()?;
+            let mut args = rest.into_iter();
+            let table = args.next().expect(usage);
+            let other = args.next().expect(usage);
+            let current = table_schema::schema_from_table(&ddb_client, &table).await?;
+            let desired = if std::path::Path::new(&other).is_file() {
+                table_schema::TableSchema::from_json(&json_doc::load_document(&other)?)?
+            } else {
+                table_schema::schema_from_table(&ddb_client, &other).await?
+            };
+            println!("--- {}\n+++ {}", table, other);
+            let old_text = table_schema::render(&current.comparable_json(), false)?;
+            let new_text = table_schema::render(&desired.comparable_json(), false)?;
+            if !diff::print_diff(&old_text, &new_text) || !apply {
+                return Ok(());
+            }
+            let plan = table_schema::plan_changes(&current, &desired);
+            for reason in &plan.skipped {
+                println!("not applied: {}", reason);
+            }
+            if plan.changes.is_empty() {
+                println!("Nothing to apply in place.");
+                return Ok(());
+            }
+            for change in &plan.changes {
+                println!("will {}", change);
+            }
+            if dry_run {
+                return Ok(());
+            }
+            if !yes && !cli::confirm_yes(&format!("Apply {} change(s) to '{}'?", plan.changes.len(), table)) {
+                println!("Aborted.");
+                return Ok(());
+            }
+            for change in &plan.changes {
+                println!("Applying: {}", change);
+                table_schema::apply_change(&ddb_client, &table, change, &desired, timeout.unwrap_or(table_schema::DEFAULT_WAIT_TIMEOUT)).await?;
+            }
+            println!("OK");
         }
         "scan-table" => {
             let table = args.next().expect("Usage: scan-table <table>");
@@ -60,7 +217,188 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             eprintln!("\nWrote {} item(s) as TSV", count);
         }
         "list-tables" => {
-            dynamodb::list_tables(&ddb_client).await?;
+            let mut rest: Vec<String> = args.collect();
+            let long = cli::take_flag(&mut rest, "--long");
+            let json = cli::take_flag(&mut rest, "--json");
+            let pattern = cli::take_opt(&mut rest, "--match").map(|g| glob::Pattern::new(&g)).transpose()?;
+            let count = dynamodb::list_tables(&ddb_client, pattern.as_ref(), long, json).await?;
+            if long && !json {
+                println!("\nTotal: {} table(s)", count);
+            }
+        }
+        "copy-table" => {
+            let mut rest: Vec<String> = args.collect();
+            let usage = "Usage: copy-table <src> <dst> [--segments 4] [--create] [--dst-profile P] [--dst-region R] [--dst-endpoint URL] [--rename OLD=NEW] [--drop ATTR] [--set ATTR=[TYPE:]VALUE] [--default ATTR=[TYPE:]VALUE] [--convert ATTR=TYPE] [--checkpoint FILE]";
+            let segments = cli::take_opt(&mut rest, "--segments").map(|n| n.parse::<usize>()).transpose()?.unwrap_or(4).max(1);
+            let create = cli::take_flag(&mut rest, "--create");
+            let dst_profile = cli::take_opt(&mut rest, "--dst-profile");
+            let dst_region = cli::take_opt(&mut rest, "--dst-region");
+            let dst_endpoint = cli::take_opt(&mut rest, "--dst-endpoint");
+            let checkpoint = cli::take_opt(&mut rest, "--checkpoint");
+            let transforms = cli::take_opts_ordered(&mut rest, item_transform::FLAGS)
+                .iter()
+                .map(|(flag, spec)| item_transform::Transform::parse(flag, spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut args = rest.into_iter();
+            let source = args.next().expect(usage);
+            let destination = args.next().expect(usage);
+            let dst_client = if dst_profile.is_some() || dst_region.is_some() || dst_endpoint.is_some() {
+                table_copy::destination_client(dst_profile.as_deref(), dst_region.as_deref(), dst_endpoint.as_deref()).await
+            } else {
+                ddb_client.clone()
+            };
+            if create && table_copy::ensure_destination(&ddb_client, &dst_client, &source, &destination).await? {
+                println!("Created table {}", destination);
+            }
+            let opts = table_copy::CopyOptions { segments, transforms: &transforms, checkpoint: checkpoint.as_deref() };
+            let stats = table_copy::copy_table(&ddb_client, &dst_client, &source, &destination, &opts).await?;
+            if stats.resumed > 0 {
+                println!("Copied {} item(s) from {} to {} ({} in earlier runs)", stats.copied, source, destination, stats.resumed);
+            } else {
+                println!("Copied {} item(s) from {} to {}", stats.copied, source, destination);
+            }
+        }
+        "migrate" => {
+            let mut rest: Vec<String> = args.collect();
+            let usage = "Usage: migrate <table> [--rename OLD=NEW] [--drop ATTR] [--set ATTR=[TYPE:]VALUE] [--default ATTR=[TYPE:]VALUE] [--convert ATTR=S|N|BOOL|SS|NS] [--segments 4] [--dry-run]";
+            let segments = cli::take_opt(&mut rest, "--segments").map(|n| n.parse::<usize>()).transpose()?.unwrap_or(4).max(1);
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let transforms = cli::take_opts_ordered(&mut rest, item_transform::FLAGS)
+                .iter()
+                .map(|(flag, spec)| item_transform::Transform::parse(flag, spec))
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut args = rest.into_iter();
+            let table = args.next().expect(usage);
+            if transforms.is_empty() {
+                panic!("{}", usage);
+            }
+            let stats = migrate::migrate_table(&ddb_client, &table, &transforms, segments, dry_run).await?;
+            println!(
+                "{} {} of {} item(s); {} failed, {} skipped after concurrent changes, {} deleted meanwhile",
+                if dry_run { "Would update" } else { "Updated" },
+                stats.updated,
+                stats.scanned,
+                stats.failed,
+                stats.conflicts,
+                stats.deleted
+            );
+            if stats.failed > 0 {
+                return Err(format!("{} item(s) could not be migrated", stats.failed).into());
+            }
+        }
+        "profile-table" => {
+            let mut rest: Vec<String> = args.collect();
+            let json = cli::take_flag(&mut rest, "--json");
+            let sample = cli::take_opt(&mut rest, "--sample").map(|n| n.parse::<u64>()).transpose()?;
+            let mut args = rest.into_iter();
+            let table = args.next().expect("Usage: profile-table <table> [--sample N] [--json]");
+            let profile = table_profile::profile_table(&ddb_client, &table, sample).await?;
+            if json {
+                println!("{:#}", profile.to_json(&table));
+            } else {
+                profile.print_text(&table);
+            }
+        }
+        "count" | "aggregate" => {
+            let mut rest: Vec<String> = args.collect();
+            let usage = if cmd == "count" {
+                "Usage: count <table> [--where COND ...] [--segments 4]   (COND: a=1, a!=x, a<5, a<=5, a>5, a>=5, a^=prefix, a?, !a)"
+            } else {
+                "Usage: aggregate <table> [attribute] [--group-by ATTR] [--where COND ...] [--segments 4] [--json]"
+            };
+            let segments = cli::take_opt(&mut rest, "--segments").map(|n| n.parse::<usize>()).transpose()?.unwrap_or(4).max(1);
+            let group_by = cli::take_opt(&mut rest, "--group-by");
+            let json = cli::take_flag(&mut rest, "--json");
+            let conditions = cli::take_opts_ordered(&mut rest, &["--where"])
+                .iter()
+                .map(|(_, c)| c.parse::<table_aggregate::Condition>())
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut args = rest.into_iter();
+            let table = args.next().expect(usage);
+            if cmd == "count" && (group_by.is_some() || json || args.len() > 0) {
+                return Err("count takes no attribute, --group-by or --json (use aggregate)".into());
+            }
+            let selection = table_aggregate::Selection::new(&ddb_client, &table, &conditions).await?;
+            if cmd == "count" {
+                let result = table_aggregate::count_items(&ddb_client, &selection, segments).await?;
+                println!("{}", result.count);
+                if !conditions.is_empty() {
+                    eprintln!("({} of {} item(s) read by the {} matched)", result.count, result.scanned, if selection.is_query() { "query" } else { "scan" });
+                }
+            } else {
+                let attribute = args.next();
+                if attribute.is_none() && group_by.is_none() {
+                    panic!("{}", usage);
+                }
+                let result = table_aggregate::aggregate(&ddb_client, &selection, attribute.as_deref(), group_by.as_deref(), segments).await?;
+                if json {
+                    println!("{:#}", result.to_json());
+                } else {
+                    result.print_text();
+                }
+            }
+        }
+        "sql" => {
+            let mut rest: Vec<String> = args.collect();
+            let usage = "Usage: sql \"<PartiQL>\" [--param VALUE ...] [--consistent] [--format table|csv|tsv|json] | sql --file statements.yaml [--format ...]";
+            let format = cli::take_opt(&mut rest, "--format").map(|f| f.parse::<dynamodb::ItemFormat>()).transpose()?.unwrap_or(dynamodb::ItemFormat::Table);
+            let consistent = cli::take_flag(&mut rest, "--consistent");
+            let file = cli::take_opt(&mut rest, "--file");
+            let params = cli::take_opts_ordered(&mut rest, &["--param"])
+                .iter()
+                .map(|(_, p)| partiql::parse_param(p))
+                .collect::<Result<Vec<_>, _>>()?;
+            if let Some(file) = file {
+                if !params.is_empty() || consistent || !rest.is_empty() {
+                    return Err("--param, --consistent and a statement can't be combined with --file (set Parameters and ConsistentRead in the file)".into());
+                }
+                let statements = partiql::load_statements(&file)?;
+                let result = partiql::execute_batch(&ddb_client, statements).await?;
+                if !result.items.is_empty() {
+                    dynamodb::print_items(&result.items, format);
+                }
+                eprintln!("{} statement(s) succeeded, {} failed", result.succeeded, result.failed);
+                if result.failed > 0 {
+                    return Err(format!("{} statement(s) failed", result.failed).into());
+                }
+            } else {
+                let mut args = rest.into_iter();
+                let statement = args.next().expect(usage);
+                let items = partiql::execute(&ddb_client, &statement, &params, consistent).await?;
+                if !items.is_empty() {
+                    dynamodb::print_items(&items, format);
+                    eprintln!("{} item(s)", items.len());
+                } else if statement.trim_start().to_ascii_uppercase().starts_with("SELECT") {
+                    println!("(no items)");
+                } else {
+                    println!("OK");
+                }
+            }
+        }
+        "transact" => {
+            let mut rest: Vec<String> = args.collect();
+            let token = cli::take_opt(&mut rest, "--token");
+            let mut args = rest.into_iter();
+            let file = args.next().expect("Usage: transact <file.json|file.yaml> [--token TOKEN]");
+            let count = transact::transact_write(&ddb_client, &file, token.as_deref()).await?;
+            println!("Committed {} operation(s)", count);
+        }
+        "transact-get" => {
+            let mut rest: Vec<String> = args.collect();
+            let format = cli::take_opt(&mut rest, "--format").map(|f| f.parse::<dynamodb::ItemFormat>()).transpose()?.unwrap_or(dynamodb::ItemFormat::Json);
+            let mut args = rest.into_iter();
+            let file = args.next().expect("Usage: transact-get <file.json|file.yaml> [--format table|csv|tsv|json]");
+            let results = transact::transact_get(&ddb_client, &file).await?;
+            if let dynamodb::ItemFormat::Json = format {
+                println!("{:#}", transact::get_results_json(&results));
+            } else {
+                let found: Vec<_> = results.iter().filter_map(|(_, item)| item.clone()).collect();
+                dynamodb::print_items(&found, format);
+            }
+            let missing = results.iter().filter(|(_, item)| item.is_none()).count();
+            if missing > 0 {
+                eprintln!("{} of {} item(s) not found", missing, results.len());
+            }
         }
         "delete-all" => {
             let table = args.next().expect("Usage: delete-all <table>");
@@ -93,13 +431,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 eprintln!("No key provided");
             } else {
                 // infer type: bool -> Bool, number -> N, otherwise -> S
-                let attribute_value = if val.eq_ignore_ascii_case("true") || val.eq_ignore_ascii_case("false") {
-                    AttributeValue::Bool(val.eq_ignore_ascii_case("true"))
-                } else if val.parse::<f64>().is_ok() {
-                    AttributeValue::N(val.to_string())
-                } else {
-                    AttributeValue::S(val.to_string())
-                };
+                let attribute_value = dynamodb::infer_attribute_value(&val);
 
                 dynamodb::set_item_attribute(&ddb_client, &table, &key_map, &attr, attribute_value).await?;
                 println!("OK");
@@ -139,13 +471,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 } else if attrs.len() == 1 {
                     let a = attrs[0];
                     for it in items {
-                        let out = it.get(a).map(|v| av_to_str(v)).unwrap_or_default();
+                        let out = it.get(a).map(av_to_str).unwrap_or_default();
                         println!("{}", out);
                     }
                 } else {
                     for it in items {
                         let row = attrs.iter()
-                            .map(|a| it.get(*a).map(|v| av_to_str(v)).unwrap_or_default())
+                            .map(|a| it.get(*a).map(av_to_str).unwrap_or_default())
                             .collect::<Vec<_>>()
                             .join("\t");
                         println!("{}", row);
@@ -156,11 +488,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
                 if let Some(map) = item {
                     if attrs.len() == 1 {
                         let a = attrs[0];
-                        let out = map.get(a).map(|v| av_to_str(v)).unwrap_or_default();
+                        let out = map.get(a).map(av_to_str).unwrap_or_default();
                         println!("{}", out);
                     } else {
                         let row = attrs.iter()
-                            .map(|a| map.get(*a).map(|v| av_to_str(v)).unwrap_or_default())
+                            .map(|a| map.get(*a).map(av_to_str).unwrap_or_default())
                             .collect::<Vec<_>>()
                             .join("\t");
                         println!("{}", row);
@@ -174,13 +506,402 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             // fallback to original behavior: first argument is bucket, optional second is table
             let bucket = cmd; // cmd was actually the bucket in this fallback
             let table_name = args.next();
-            let count = s3::list_s3_objects(&s3_client, &bucket).await?;
+            let count = s3::list_s3_objects(&s3_client, &bucket, "", &s3::ListFilter::default()).await?;
             println!("\nTotal: {} object(s)", count);
             if let Some(tbl) = table_name {
-                dynamodb::describe_table_schema(&ddb_client, &tbl).await?;
+                dynamodb::describe_table_schema(&ddb_client, &tbl, false).await?;
             }
         }
     }
 
     Ok(())
 }
+
+/// Pull the `list-s3` style object filters out of `rest`.
+fn take_list_filter(rest: &mut Vec<String>) -> Result<s3::ListFilter, Box<dyn std::error::Error + Send + Sync>> {
+    Ok(s3::ListFilter {
+        glob: cli::take_opt(rest, "--match").map(|g| glob::Pattern::new(&g)).transpose()?,
+        regex: cli::take_opt(rest, "--regex").map(|r| regex::Regex::new(&r)).transpose()?,
+        larger_than: cli::take_opt(rest, "--larger-than").map(|v| cli::parse_size(&v)).transpose()?,
+        modified_since: cli::take_opt(rest, "--modified-since").map(|v| cli::parse_time(&v)).transpose()?,
+    })
+}
+
+async fn run_s3(
+    client: &S3Client,
+    sub: &str,
+    mut rest: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    match sub {
+        "stat" => {
+            let json = cli::take_flag(&mut rest, "--json");
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect("Usage: s3 stat <bucket> <key> [--json]");
+            let key = args.next().expect("Usage: s3 stat <bucket> <key> [--json]");
+            if !s3::stat_object(client, &bucket, &key, json).await? {
+                std::process::exit(1);
+            }
+        }
+        "presign" => {
+            let usage = "Usage: s3 presign <bucket> <key|prefix> [--method get|put] [--expires 1h] [--content-type T] [--recursive --output FILE]";
+            let recursive = cli::take_flag(&mut rest, "--recursive");
+            let method = cli::take_opt(&mut rest, "--method");
+            let expires = cli::take_opt(&mut rest, "--expires");
+            let content_type = cli::take_opt(&mut rest, "--content-type");
+            let output = cli::take_opt(&mut rest, "--output");
+            let mut args = rest.into_iter();
+            let method: s3::PresignMethod = method.as_deref().unwrap_or("get").parse()?;
+            let expires = cli::parse_duration(expires.as_deref().unwrap_or("1h"))?;
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            if content_type.is_some() && method != s3::PresignMethod::Put {
+                eprintln!("--content-type only applies to --method put; ignoring");
+            }
+            let content_type = content_type.filter(|_| method == s3::PresignMethod::Put);
+            if recursive {
+                let count = match &output {
+                    Some(path) => {
+                        let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+                        let n = s3::presign_prefix(client, &bucket, &key, method, expires, content_type.as_deref(), &mut file).await?;
+                        std::io::Write::flush(&mut file)?;
+                        n
+                    }
+                    None => {
+                        let mut stdout = std::io::stdout();
+                        s3::presign_prefix(client, &bucket, &key, method, expires, content_type.as_deref(), &mut stdout).await?
+                    }
+                };
+                eprintln!("Presigned {} object(s)", count);
+            } else {
+                let req = s3::presign_object(client, &bucket, &key, method, expires, content_type.as_deref()).await?;
+                println!("{}", req.uri());
+                if let Some(ct) = &content_type {
+                    eprintln!("Upload must send header: Content-Type: {}", ct);
+                }
+            }
+        }
+        "rm" => {
+            let usage = "Usage: s3 rm <bucket> <key|prefix> [--recursive] [--dry-run] [--include GLOB] [--exclude GLOB]";
+            let recursive = cli::take_flag(&mut rest, "--recursive");
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let filter = s3::KeyFilter::new(&cli::take_filters(&mut rest))?;
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            let deleted = s3::remove(client, &bucket, &key, recursive, &filter, dry_run).await?;
+            println!("\n{} {} object(s)", if dry_run { "Would delete" } else { "Deleted" }, deleted);
+        }
+        "cp" | "mv" => {
+            let usage = "Usage: s3 cp|mv <src-bucket> <src-key|prefix> <dst-bucket> <dst-key|prefix> [--recursive] [--dry-run] [--include GLOB] [--exclude GLOB] [--keys-from FILE|-]";
+            let recursive = cli::take_flag(&mut rest, "--recursive");
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let keys_from = cli::take_opt(&mut rest, "--keys-from");
+            let mut filter = s3::KeyFilter::new(&cli::take_filters(&mut rest))?;
+            if let Some(path) = keys_from {
+                filter = filter.only_keys(cli::read_lines(&path)?);
+            }
+            let mut args = rest.into_iter();
+            let src_bucket = args.next().expect(usage);
+            let src = args.next().expect(usage);
+            let dst_bucket = args.next().expect(usage);
+            let dst = args.next().expect(usage);
+            let moving = sub == "mv";
+            let count = s3::copy(client, &src_bucket, &src, &dst_bucket, &dst, recursive, &filter, dry_run, moving).await?;
+            let verb = match (dry_run, moving) {
+                (true, true) => "Would move",
+                (true, false) => "Would copy",
+                (false, true) => "Moved",
+                (false, false) => "Copied",
+            };
+            println!("\n{} {} object(s)", verb, count);
+        }
+        "versions" => {
+            let json = cli::take_flag(&mut rest, "--json");
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect("Usage: s3 versions <bucket> [prefix] [--json]");
+            let prefix = args.next().unwrap_or_default();
+            let count = s3::print_versions(client, &bucket, &prefix, json).await?;
+            if !json {
+                println!("\nTotal: {} version(s)", count);
+            }
+        }
+        "restore-version" => {
+            let usage = "Usage: s3 restore-version <bucket> <key> <version-id>";
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            let version_id = args.next().expect(usage);
+            s3::restore_version(client, &bucket, &key, &version_id).await?;
+            println!("OK");
+        }
+        "undelete" => {
+            let usage = "Usage: s3 undelete <bucket> <key>";
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            if s3::undelete(client, &bucket, &key).await? {
+                println!("OK");
+            } else {
+                println!("s3://{}/{} is not deleted (no current delete marker).", bucket, key);
+            }
+        }
+        "purge-versions" => {
+            let usage = "Usage: s3 purge-versions <bucket> [prefix] --older-than 30d [--dry-run]";
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let older_than = cli::take_opt(&mut rest, "--older-than").expect(usage);
+            let older_than = cli::parse_duration(&older_than)?;
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let prefix = args.next().unwrap_or_default();
+            let deleted = s3::purge_versions(client, &bucket, &prefix, older_than, dry_run).await?;
+            println!("\n{} {} noncurrent version(s)", if dry_run { "Would delete" } else { "Deleted" }, deleted);
+        }
+        "tag" => {
+            let usage = "Usage: s3 tag get|set|delete <bucket> <key|prefix> [k=v ... | k ...] [--replace] [--recursive] [--match GLOB] [--regex RE] [--larger-than SIZE] [--modified-since TIME] [--dry-run]";
+            let json = cli::take_flag(&mut rest, "--json");
+            let replace = cli::take_flag(&mut rest, "--replace");
+            let recursive = cli::take_flag(&mut rest, "--recursive");
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let filter = take_list_filter(&mut rest)?;
+            let mut args = rest.into_iter();
+            let action = args.next().expect(usage);
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            let op = match action.as_str() {
+                "get" => {
+                    s3::print_tags(client, &bucket, &key, json).await?;
+                    return Ok(());
+                }
+                "set" => {
+                    let mut tags = Vec::new();
+                    for kv in args {
+                        match kv.split_once('=') {
+                            Some((k, v)) => tags.push((k.to_string(), v.to_string())),
+                            None => return Err(format!("expected key=value, got '{}'", kv).into()),
+                        }
+                    }
+                    if tags.is_empty() {
+                        panic!("{}", usage);
+                    }
+                    s3::TagOp::Set { tags, replace }
+                }
+                "delete" => s3::TagOp::Delete { keys: args.collect() },
+                _ => panic!("{}", usage),
+            };
+            if recursive {
+                let (ok, failed) = s3::apply_tags_bulk(client, &bucket, &key, &filter, &op, dry_run).await?;
+                println!("\n{} {} object(s), {} failed", if dry_run { "Would tag" } else { "Tagged" }, ok, failed);
+            } else if dry_run {
+                println!("(dry run) tag: s3://{}/{}", bucket, key);
+            } else {
+                s3::apply_tags(client, &bucket, &key, &op).await?;
+                println!("OK");
+            }
+        }
+        "restore" => {
+            let usage = "Usage: s3 restore <bucket> <key|prefix> [--days 7] [--tier bulk|standard|expedited] [--recursive [filters]] [--dry-run]";
+            let recursive = cli::take_flag(&mut rest, "--recursive");
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let days: i32 = cli::take_opt(&mut rest, "--days").unwrap_or_else(|| "7".into()).parse()?;
+            let tier = s3::parse_tier(&cli::take_opt(&mut rest, "--tier").unwrap_or_else(|| "standard".into()))?;
+            let filter = take_list_filter(&mut rest)?;
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            let (requested, in_progress, failed) =
+                s3::restore_objects(client, &bucket, &key, recursive, &filter, days, tier, dry_run).await?;
+            println!(
+                "\n{} {} restore(s), {} already in progress, {} failed",
+                if dry_run { "Would request" } else { "Requested" },
+                requested,
+                in_progress,
+                failed
+            );
+        }
+        "restore-status" => {
+            let usage = "Usage: s3 restore-status <bucket> <key|prefix> [--recursive [filters]] [--json] [--watch 15m]";
+            let recursive = cli::take_flag(&mut rest, "--recursive");
+            let json = cli::take_flag(&mut rest, "--json");
+            let watch = cli::take_opt(&mut rest, "--watch").map(|w| cli::parse_duration(&w)).transpose()?;
+            let filter = take_list_filter(&mut rest)?;
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            loop {
+                let in_progress = s3::restore_status(client, &bucket, &key, recursive, &filter, json).await?;
+                match watch {
+                    Some(interval) if in_progress > 0 => tokio::time::sleep(interval).await,
+                    _ => break,
+                }
+            }
+        }
+        "lifecycle" | "cors" | "policy" | "encryption" | "public-access-block" => {
+            let usage = format!("Usage: s3 {} get|put|delete <bucket> [file.json|file.yaml] [--dry-run] [--yes]", sub);
+            let kind: bucket_config::ConfigKind = sub.parse()?;
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let yes = cli::take_flag(&mut rest, "--yes");
+            let mut args = rest.into_iter();
+            let action = args.next().expect(&usage);
+            let bucket = args.next().expect(&usage);
+            let current = bucket_config::get_config(client, &bucket, kind).await?;
+            let current_text = current.as_ref().map(|v| format!("{:#}", v)).unwrap_or_default();
+            match action.as_str() {
+                "get" => match &current {
+                    Some(_) => println!("{}", current_text),
+                    None => println!("No {} configuration on bucket '{}'.", sub, bucket),
+                },
+                "put" => {
+                    let file = args.next().expect(&usage);
+                    let doc = bucket_config::load_config(kind, &file)?;
+                    let new_text = format!("{:#}", bucket_config::normalize(kind, &doc)?);
+                    if !diff::print_diff(&current_text, &new_text) || dry_run {
+                        return Ok(());
+                    }
+                    if !yes && !cli::confirm_yes(&format!("Apply this {} configuration to '{}'?", sub, bucket)) {
+                        println!("Aborted.");
+                        return Ok(());
+                    }
+                    bucket_config::put_config(client, &bucket, kind, &doc).await?;
+                    println!("OK");
+                }
+                "delete" => {
+                    if current.is_none() {
+                        println!("No {} configuration on bucket '{}'.", sub, bucket);
+                        return Ok(());
+                    }
+                    diff::print_diff(&current_text, "");
+                    if dry_run {
+                        return Ok(());
+                    }
+                    if !yes && !cli::confirm_yes(&format!("Delete the {} configuration of '{}'?", sub, bucket)) {
+                        println!("Aborted.");
+                        return Ok(());
+                    }
+                    bucket_config::delete_config(client, &bucket, kind).await?;
+                    println!("OK");
+                }
+                _ => panic!("{}", usage),
+            }
+        }
+        "multipart" => {
+            let usage = "Usage: s3 multipart list <bucket> [prefix] [--json] | s3 multipart abort <bucket> [prefix] --older-than 7d [--dry-run]";
+            let json = cli::take_flag(&mut rest, "--json");
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let older_than = cli::take_opt(&mut rest, "--older-than");
+            let mut args = rest.into_iter();
+            let action = args.next().expect(usage);
+            let bucket = args.next().expect(usage);
+            let prefix = args.next().unwrap_or_default();
+            match action.as_str() {
+                "list" => {
+                    let count = s3::print_multipart_uploads(client, &bucket, &prefix, json).await?;
+                    if !json {
+                        println!("\nTotal: {} upload(s)", count);
+                    }
+                }
+                "abort" => {
+                    let older_than = cli::parse_duration(&older_than.expect(usage))?;
+                    let aborted = s3::abort_multipart_uploads(client, &bucket, &prefix, older_than, dry_run).await?;
+                    println!("\n{} {} upload(s)", if dry_run { "Would abort" } else { "Aborted" }, aborted);
+                }
+                _ => panic!("{}", usage),
+            }
+        }
+        "grep" => {
+            let usage = "Usage: s3 grep <pattern> <bucket> [prefix] [-i] [--max-bytes 10MB] [--match GLOB] [--regex RE] [--larger-than SIZE] [--modified-since TIME]";
+            let ignore_case = cli::take_flag(&mut rest, "-i");
+            let max_bytes = cli::parse_size(&cli::take_opt(&mut rest, "--max-bytes").unwrap_or_else(|| "10MB".into()))?;
+            let filter = take_list_filter(&mut rest)?;
+            let mut args = rest.into_iter();
+            let pattern = args.next().expect(usage);
+            let bucket = args.next().expect(usage);
+            let prefix = args.next().unwrap_or_default();
+            let re = regex::RegexBuilder::new(&pattern).case_insensitive(ignore_case).build()?;
+            let count = s3::grep_objects(client, &bucket, &prefix, &filter, &re, max_bytes).await?;
+            eprintln!("\n{} matching line(s)", count);
+        }
+        "diff" => {
+            let usage = "Usage: s3 diff <bucket-a>[/prefix] <bucket-b>[/prefix] [--size-only] [--keys]";
+            let size_only = cli::take_flag(&mut rest, "--size-only");
+            let keys_only = cli::take_flag(&mut rest, "--keys");
+            let mut args = rest.into_iter();
+            let (a_bucket, a_prefix) = s3::parse_s3_path(&args.next().expect(usage));
+            let (b_bucket, b_prefix) = s3::parse_s3_path(&args.next().expect(usage));
+            let summary = s3::diff_prefixes(client, &a_bucket, &a_prefix, &b_bucket, &b_prefix, size_only, keys_only).await?;
+            eprintln!(
+                "\n{} only in A, {} only in B, {} differ, {} identical",
+                summary.only_a, summary.only_b, summary.changed, summary.same
+            );
+        }
+        "select" => {
+            let usage = "Usage: s3 select <bucket> <key> \"<SQL>\" [--format csv|tsv|json|parquet] [--local]";
+            let format = cli::take_opt(&mut rest, "--format");
+            let local = cli::take_flag(&mut rest, "--local");
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let key = args.next().expect(usage);
+            let sql = args.next().expect(usage);
+            let format = match format {
+                Some(f) => f.parse::<s3::SelectFormat>()?,
+                None => s3::SelectFormat::from_key(&key),
+            };
+            s3::select_object(client, &bucket, &key, &sql, format, local).await?;
+        }
+        "archive" => {
+            let usage = "Usage: s3 archive <bucket> <prefix> --to <file|s3://bucket/key|-> [--format tar|tar.gz|tar.zst|zip] [--match GLOB] [--regex RE] [--larger-than SIZE] [--modified-since TIME]";
+            let to = cli::take_opt(&mut rest, "--to").expect(usage);
+            let format = cli::take_opt(&mut rest, "--format").map(|f| f.parse::<archive::ArchiveFormat>()).transpose()?;
+            let filter = take_list_filter(&mut rest)?;
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let prefix = args.next().expect(usage);
+            let dest = archive::Location::parse(&to);
+            let format = dest.format(format).or_else(|e| if to == "-" { Ok(archive::ArchiveFormat::Tar) } else { Err(e) })?;
+            let stats = archive::archive_prefix(client, &bucket, &prefix, &filter, &dest, format).await?;
+            eprintln!("Archived {} object(s), {} bytes, to {}", stats.objects, stats.bytes, dest);
+        }
+        "unarchive" => {
+            let usage = "Usage: s3 unarchive <file|s3://bucket/key|-> <bucket> [prefix] [--format tar|tar.gz|tar.zst|zip] [--dry-run]";
+            let format = cli::take_opt(&mut rest, "--format").map(|f| f.parse::<archive::ArchiveFormat>()).transpose()?;
+            let dry_run = cli::take_flag(&mut rest, "--dry-run");
+            let mut args = rest.into_iter();
+            let src = archive::Location::parse(&args.next().expect(usage));
+            let bucket = args.next().expect(usage);
+            let prefix = args.next().unwrap_or_default();
+            let format = src.format(format)?;
+            let count = archive::unarchive(client, src, format, &bucket, &prefix, dry_run).await?;
+            let verb = if dry_run { "Would upload" } else { "Uploaded" };
+            println!("\n{} {} object(s)", verb, count);
+        }
+        "mb" => {
+            let region = cli::take_opt(&mut rest, "--region");
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect("Usage: s3 mb <bucket> [--region R]");
+            s3::make_bucket(client, &bucket, region.as_deref()).await?;
+            println!("make_bucket: {}", bucket);
+        }
+        "rb" => {
+            let usage = "Usage: s3 rb <bucket> [--force] [--yes]";
+            let force = cli::take_flag(&mut rest, "--force");
+            let yes = cli::take_flag(&mut rest, "--yes");
+            let mut args = rest.into_iter();
+            let bucket = args.next().expect(usage);
+            let what = if force { " and ALL of its contents (every version, delete marker and pending upload)" } else { "" };
+            let prompt = format!("Delete bucket '{}'{}? Type the bucket name to confirm: ", bucket, what);
+            if !yes && !cli::confirm(&prompt, &bucket) {
+                println!("Aborted.");
+                return Ok(());
+            }
+            if force {
+                let (deleted, aborted) = s3::empty_bucket(client, &bucket).await?;
+                println!("Deleted {} object version(s), aborted {} multipart upload(s)", deleted, aborted);
+            }
+            s3::remove_bucket(client, &bucket).await?;
+            println!("remove_bucket: {}", bucket);
+        }
+        other => {
+            return Err(format!("Unknown s3 subcommand: {}", other).into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,291 @@
+// Rewrite the items of a table in place (`migrate`): rename, convert, default
+// or remove attributes with the `item_transform` rules.
+//
+// Each changed item gets one UpdateItem whose condition requires the item to
+// still exist and every touched attribute to still hold the value that was
+// scanned, so a concurrent writer's change is never overwritten (the item is
+// re-read and the rules applied again) and a deleted item is never recreated.
+
+use crate::dynamodb::item_to_json;
+use crate::item_transform::Transform;
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::types::{AttributeValue, KeyType};
+use futures::{StreamExt, TryStreamExt};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Item = HashMap<String, AttributeValue>;
+
+/// UpdateItem calls each segment keeps in flight.
+const UPDATES_IN_FLIGHT: usize = 16;
+/// Times an item is re-read and retried after a concurrent change.
+const MAX_ATTEMPTS: u32 = 3;
+/// Print a progress line every this many scanned items.
+const PROGRESS_EVERY: u64 = 10_000;
+
+pub struct MigrateStats {
+    pub scanned: u64,
+    pub updated: u64,
+    /// Items that kept changing under us for `MAX_ATTEMPTS` attempts.
+    pub conflicts: u64,
+    /// Items deleted between the scan and their update.
+    pub deleted: u64,
+    /// Items a rule couldn't be applied to (e.g. a non-numeric string for `N`).
+    pub failed: u64,
+}
+
+/// UpdateItem expression that turns `old` into `new`, conditioned on `old` and
+/// on the item still existing (checked through its hash key).
+struct Update {
+    expression: String,
+    condition: String,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+    /// Human-readable summary for dry runs.
+    summary: String,
+}
+
+fn plan_update(old: &Item, new: &Item, hash_key: &str) -> Option<Update> {
+    let attrs: BTreeSet<&String> = old.keys().chain(new.keys()).filter(|a| old.get(*a) != new.get(*a)).collect();
+    if attrs.is_empty() {
+        return None;
+    }
+    let mut names = HashMap::from([("#k".to_string(), hash_key.to_string())]);
+    let mut values = HashMap::new();
+    let (mut set, mut remove, mut conditions) = (Vec::new(), Vec::new(), vec!["attribute_exists(#k)".to_string()]);
+    let (mut set_names, mut remove_names) = (Vec::new(), Vec::new());
+    for (i, attr) in attrs.into_iter().enumerate() {
+        let name = format!("#a{}", i);
+        names.insert(name.clone(), attr.clone());
+        match old.get(attr) {
+            Some(v) => {
+                values.insert(format!(":o{}", i), v.clone());
+                conditions.push(format!("{} = :o{}", name, i));
+            }
+            None => conditions.push(format!("attribute_not_exists({})", name)),
+        }
+        match new.get(attr) {
+            Some(v) => {
+                values.insert(format!(":v{}", i), v.clone());
+                set.push(format!("{} = :v{}", name, i));
+                set_names.push(attr.as_str());
+            }
+            None => {
+                remove.push(name);
+                remove_names.push(attr.as_str());
+            }
+        }
+    }
+    let mut expression = Vec::new();
+    let mut summary = Vec::new();
+    if !set.is_empty() {
+        expression.push(format!("SET {}", set.join(", ")));
+        summary.push(format!("SET {}", set_names.join(", ")));
+    }
+    if !remove.is_empty() {
+        expression.push(format!("REMOVE {}", remove.join(", ")));
+        summary.push(format!("REMOVE {}", remove_names.join(", ")));
+    }
+    Some(Update { expression: expression.join(" "), condition: conditions.join(" AND "), names, values, summary: summary.join("; ") })
+}
+
+/// One migration run: the rules plus counters shared by all segments.
+struct Migration<'a> {
+    client: &'a DdbClient,
+    table: &'a str,
+    key_attrs: Vec<String>,
+    hash_key: String,
+    transforms: &'a [Transform],
+    dry_run: bool,
+    scanned: AtomicU64,
+    updated: AtomicU64,
+    conflicts: AtomicU64,
+    deleted: AtomicU64,
+    failed: AtomicU64,
+}
+
+impl Migration<'_> {
+    async fn migrate_item(&self, mut item: Item) -> Result<(), BoxError> {
+        let key: Item = self.key_attrs.iter().filter_map(|k| item.get(k).map(|v| (k.clone(), v.clone()))).collect();
+        let key_text = item_to_json(&key).to_string();
+        for _ in 0..MAX_ATTEMPTS {
+            let mut new = item.clone();
+            if let Err(e) = self.transforms.iter().try_for_each(|t| t.apply(&mut new)) {
+                eprintln!("{}: {}", key_text, e);
+                self.failed.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+            let Some(update) = plan_update(&item, &new, &self.hash_key) else { return Ok(()) };
+            if self.dry_run {
+                println!("{}: {}", key_text, update.summary);
+                self.updated.fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+            let result = self
+                .client
+                .update_item()
+                .table_name(self.table)
+                .set_key(Some(key.clone()))
+                .update_expression(update.expression)
+                .condition_expression(update.condition)
+                .set_expression_attribute_names(Some(update.names))
+                .set_expression_attribute_values((!update.values.is_empty()).then_some(update.values))
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from);
+            match result {
+                Ok(_) => {
+                    self.updated.fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+                Err(aws_sdk_dynamodb::Error::ConditionalCheckFailedException(_)) => {
+                    let fresh = self
+                        .client
+                        .get_item()
+                        .table_name(self.table)
+                        .set_key(Some(key.clone()))
+                        .consistent_read(true)
+                        .send()
+                        .await
+                        .map_err(aws_sdk_dynamodb::Error::from)?;
+                    match fresh.item {
+                        Some(current) => item = current,
+                        None => {
+                            eprintln!("{}: deleted concurrently, skipped", key_text);
+                            self.deleted.fetch_add(1, Ordering::Relaxed);
+                            return Ok(());
+                        }
+                    }
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        eprintln!("{}: changed concurrently {} times, skipped", key_text, MAX_ATTEMPTS);
+        self.conflicts.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    }
+
+    async fn migrate_segment(&self, segment: usize, segments: usize) -> Result<(), BoxError> {
+        let mut start_key = None;
+        loop {
+            let page = self
+                .client
+                .scan()
+                .table_name(self.table)
+                .segment(segment as i32)
+                .total_segments(segments as i32)
+                .consistent_read(true)
+                .set_exclusive_start_key(start_key)
+                .send()
+                .await
+                .map_err(aws_sdk_dynamodb::Error::from)?;
+            let items = page.items.unwrap_or_default();
+            let count = items.len() as u64;
+            futures::stream::iter(items)
+                .map(|item| self.migrate_item(item))
+                .buffer_unordered(UPDATES_IN_FLIGHT)
+                .try_collect::<Vec<()>>()
+                .await?;
+            let before = self.scanned.fetch_add(count, Ordering::Relaxed);
+            if (before + count) / PROGRESS_EVERY > before / PROGRESS_EVERY {
+                eprintln!("{} item(s) scanned, {} updated...", before + count, self.updated.load(Ordering::Relaxed));
+            }
+            start_key = page.last_evaluated_key;
+            if start_key.is_none() {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Apply `transforms` to every item of `table`; with `dry_run`, print each
+/// planned change instead of writing it.
+pub async fn migrate_table(
+    client: &DdbClient,
+    table: &str,
+    transforms: &[Transform],
+    segments: usize,
+    dry_run: bool,
+) -> Result<MigrateStats, BoxError> {
+    let resp = client.describe_table().table_name(table).send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+    let key_schema = resp.table().map(|t| t.key_schema()).unwrap_or_default();
+    let key_attrs: Vec<String> = key_schema.iter().map(|k| k.attribute_name().to_string()).collect();
+    let hash_key = key_schema
+        .iter()
+        .find(|k| *k.key_type() == KeyType::Hash)
+        .map(|k| k.attribute_name().to_string())
+        .ok_or_else(|| format!("{} has no hash key", table))?;
+    if let Some(k) = transforms.iter().flat_map(|t| t.attributes()).find(|a| key_attrs.iter().any(|k| k == a)) {
+        return Err(format!("'{}' is part of the primary key of {}; key attributes can't be migrated in place (use copy-table)", k, table).into());
+    }
+    let migration = Migration {
+        client,
+        table,
+        key_attrs,
+        hash_key,
+        transforms,
+        dry_run,
+        scanned: AtomicU64::new(0),
+        updated: AtomicU64::new(0),
+        conflicts: AtomicU64::new(0),
+        deleted: AtomicU64::new(0),
+        failed: AtomicU64::new(0),
+    };
+    futures::future::try_join_all((0..segments).map(|segment| migration.migrate_segment(segment, segments))).await?;
+    Ok(MigrateStats {
+        scanned: migration.scanned.into_inner(),
+        updated: migration.updated.into_inner(),
+        conflicts: migration.conflicts.into_inner(),
+        deleted: migration.deleted.into_inner(),
+        failed: migration.failed.into_inner(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(pairs: &[(&str, AttributeValue)]) -> Item {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.clone())).collect()
+    }
+
+    fn s(v: &str) -> AttributeValue {
+        AttributeValue::S(v.to_string())
+    }
+
+    #[test]
+    fn unchanged_item_needs_no_update() {
+        let old = item(&[("pk", s("a")), ("x", s("1"))]);
+        assert!(plan_update(&old, &old.clone(), "pk").is_none());
+    }
+
+    #[test]
+    fn set_and_remove_are_conditioned_on_scanned_values() {
+        let old = item(&[("pk", s("a")), ("n", s("1")), ("old", s("x"))]);
+        let new = item(&[("pk", s("a")), ("n", AttributeValue::N("1".into())), ("add", s("y"))]);
+        let u = plan_update(&old, &new, "pk").unwrap();
+        // Attributes are numbered in name order: add, n, old.
+        assert_eq!(u.expression, "SET #a0 = :v0, #a1 = :v1 REMOVE #a2");
+        assert_eq!(u.condition, "attribute_exists(#k) AND attribute_not_exists(#a0) AND #a1 = :o1 AND #a2 = :o2");
+        assert_eq!(u.summary, "SET add, n; REMOVE old");
+        assert_eq!(u.names["#k"], "pk");
+        assert_eq!(u.names["#a0"], "add");
+        assert_eq!(u.names["#a2"], "old");
+        assert_eq!(u.values.len(), 4);
+        assert_eq!(u.values[":v0"], s("y"));
+        assert_eq!(u.values[":v1"], AttributeValue::N("1".into()));
+        assert_eq!(u.values[":o1"], s("1"));
+        assert_eq!(u.values[":o2"], s("x"));
+    }
+
+    #[test]
+    fn adding_attributes_still_requires_the_item() {
+        let old = item(&[("pk", s("a"))]);
+        let new = item(&[("pk", s("a")), ("d", s("z"))]);
+        let u = plan_update(&old, &new, "pk").unwrap();
+        assert_eq!(u.expression, "SET #a0 = :v0");
+        assert_eq!(u.condition, "attribute_exists(#k) AND attribute_not_exists(#a0)");
+        assert_eq!(u.values.len(), 1);
+    }
+}
@@ -0,0 +1,111 @@
+// Run PartiQL statements against DynamoDB (`sql`).
+//
+// A single statement goes through ExecuteStatement, following NextToken until
+// every page is read. A file of statements (the `--statements` shape of
+// `aws dynamodb batch-execute-statement`, as JSON or YAML) goes through
+// BatchExecuteStatement, 25 statements per call.
+
+use crate::dynamodb::{attr_from_json, parse_typed_value};
+use crate::json_doc::{bool_field, field, list_field, load_document, str_field};
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::types::{AttributeValue, BatchStatementRequest};
+use serde_json::Value;
+use std::collections::HashMap;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Item = HashMap<String, AttributeValue>;
+
+/// BatchExecuteStatement accepts at most 25 statements.
+const BATCH_SIZE: usize = 25;
+
+/// A `--param`: DynamoDB JSON (`{"L": [...]}`) or a possibly typed scalar (`S:abc`, `N:5`, `42`).
+pub fn parse_param(s: &str) -> Result<AttributeValue, String> {
+    if s.trim_start().starts_with('{') {
+        let v: Value = serde_json::from_str(s).map_err(|e| format!("invalid JSON parameter '{}': {}", s, e))?;
+        attr_from_json(&v)
+    } else {
+        parse_typed_value(s)
+    }
+}
+
+/// Run one statement and return every item it produces (all pages).
+pub async fn execute(client: &DdbClient, statement: &str, params: &[AttributeValue], consistent: bool) -> Result<Vec<Item>, BoxError> {
+    let mut items = Vec::new();
+    let mut next_token = None;
+    loop {
+        let resp = client
+            .execute_statement()
+            .statement(statement)
+            .set_parameters((!params.is_empty()).then(|| params.to_vec()))
+            .consistent_read(consistent)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        items.extend(resp.items.unwrap_or_default());
+        next_token = resp.next_token;
+        if next_token.is_none() {
+            return Ok(items);
+        }
+    }
+}
+
+/// Read batch statements from a JSON/YAML file: a list of
+/// `{Statement, Parameters, ConsistentRead}` (parameters in DynamoDB JSON), or
+/// an object with that list under `Statements`.
+pub fn load_statements(path: &str) -> Result<Vec<BatchStatementRequest>, BoxError> {
+    let doc = load_document(path)?;
+    let list = field(&doc, "Statements").unwrap_or(&doc);
+    let list = list.as_array().ok_or("expected a list of statements (or {\"Statements\": [...]})")?;
+    list.iter()
+        .enumerate()
+        .map(|(i, s)| {
+            let statement = str_field(s, "Statement")?.ok_or_else(|| format!("statement {} has no Statement", i + 1))?;
+            let params = list_field(s, "Parameters")?.iter().map(attr_from_json).collect::<Result<Vec<_>, _>>()?;
+            Ok(BatchStatementRequest::builder()
+                .statement(statement)
+                .set_parameters((!params.is_empty()).then_some(params))
+                .set_consistent_read(bool_field(s, "ConsistentRead")?)
+                .build()?)
+        })
+        .collect()
+}
+
+pub struct BatchResult {
+    /// Items returned by SELECT statements, in statement order.
+    pub items: Vec<Item>,
+    pub succeeded: usize,
+    pub failed: usize,
+}
+
+/// Run `statements` in groups of 25; each failed statement is reported on
+/// stderr with its (1-based) position in the file.
+pub async fn execute_batch(client: &DdbClient, statements: Vec<BatchStatementRequest>) -> Result<BatchResult, BoxError> {
+    let mut result = BatchResult { items: Vec::new(), succeeded: 0, failed: 0 };
+    for (chunk_index, chunk) in statements.chunks(BATCH_SIZE).enumerate() {
+        let resp = client
+            .batch_execute_statement()
+            .set_statements(Some(chunk.to_vec()))
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        for (i, r) in resp.responses.unwrap_or_default().into_iter().enumerate() {
+            match r.error() {
+                Some(e) => {
+                    result.failed += 1;
+                    eprintln!(
+                        "statement {}: {}: {}",
+                        chunk_index * BATCH_SIZE + i + 1,
+                        e.code().map(|c| c.as_str()).unwrap_or("Error"),
+                        e.message().unwrap_or_default()
+                    );
+                }
+                None => {
+                    result.succeeded += 1;
+                    result.items.extend(r.item);
+                }
+            }
+        }
+    }
+    Ok(result)
+}
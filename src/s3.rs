@@ -1,9 +1,86 @@
 use aws_sdk_s3::Client as S3Client;
+use aws_sdk_s3::presigning::{PresignedRequest, PresigningConfig};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sdk_s3::config::retry::RetryConfig;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata};
+use aws_sdk_s3::types::{
+    BucketLocationConstraint, CompletedMultipartUpload, CompletedPart, CreateBucketConfiguration, Delete,
+    GlacierJobParameters, MultipartUpload, Object, ObjectIdentifier, RestoreRequest, Tag, Tagging, Tier,
+};
+use futures::StreamExt;
+use std::collections::HashSet;
+use std::io::{BufRead, Write};
+use std::time::Duration;
 
-pub async fn list_s3_objects(client: &S3Client, bucket: &str) -> Result<usize, aws_sdk_s3::Error> {
+/// DeleteObjects accepts at most 1000 keys per request.
+const DELETE_BATCH_SIZE: usize = 1000;
+/// Largest object a single CopyObject call can copy (5 GiB).
+const MAX_COPY_OBJECT_SIZE: i64 = 5 * 1024 * 1024 * 1024;
+const MULTIPART_COPY_PART_SIZE: i64 = 512 * 1024 * 1024;
+const COPY_CONCURRENCY: usize = 8;
+const BUCKET_SUMMARY_CONCURRENCY: usize = 8;
+const BULK_CONCURRENCY: usize = 16;
+/// Retry budget for bulk operations, which are the ones likely to hit SlowDown.
+const BULK_MAX_ATTEMPTS: u32 = 8;
+
+/// Client-side filters for `list-s3`. Every set condition must hold.
+#[derive(Default)]
+pub struct ListFilter {
+    pub glob: Option<glob::Pattern>,
+    pub regex: Option<regex::Regex>,
+    pub larger_than: Option<i64>,
+    pub modified_since: Option<DateTime>,
+}
+
+impl ListFilter {
+    pub fn matches(&self, object: &Object) -> bool {
+        let key = object.key().unwrap_or_default();
+        if self.glob.as_ref().is_some_and(|g| !g.matches(key)) {
+            return false;
+        }
+        if self.regex.as_ref().is_some_and(|r| !r.is_match(key)) {
+            return false;
+        }
+        if self.larger_than.is_some_and(|min| object.size().unwrap_or(0) <= min) {
+            return false;
+        }
+        if let Some(since) = &self.modified_since {
+            match object.last_modified() {
+                Some(t) if t.as_nanos() >= since.as_nanos() => {}
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// The literal leading part of the glob (up to the first wildcard), which
+    /// can be sent to S3 as the listing prefix.
+    fn glob_prefix(&self) -> Option<&str> {
+        let pat = self.glob.as_ref()?.as_str();
+        let end = pat.find(['*', '?', '[', ']']).unwrap_or(pat.len());
+        Some(&pat[..end])
+    }
+
+    /// Narrow `prefix` using the glob's literal prefix when the two agree.
+    pub fn effective_prefix<'a>(&'a self, prefix: &'a str) -> &'a str {
+        match self.glob_prefix() {
+            Some(g) if g.len() > prefix.len() && g.starts_with(prefix) => g,
+            _ => prefix,
+        }
+    }
+}
+
+pub async fn list_s3_objects(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    filter: &ListFilter,
+) -> Result<usize, aws_sdk_s3::Error> {
     let mut paginator = client
         .list_objects_v2()
         .bucket(bucket)
+        .prefix(filter.effective_prefix(prefix))
         .into_paginator()
         .send();
 
@@ -13,6 +90,9 @@ pub async fn list_s3_objects(client: &S3Client, bucket: &str) -> Result<usize, a
         let contents = page.contents();
         if !contents.is_empty() {
             for object in contents {
+                if !filter.matches(object) {
+                    continue;
+                }
                 match object.key() {
                     Some(k) => println!("{}", k),
                     None => println!("(no key)"),
@@ -41,3 +121,1878 @@ pub async fn list_s3_buckets(client: &S3Client) -> Result<usize, aws_sdk_s3::Err
     }
     Ok(buckets.len())
 }
+
+fn fmt_time(t: &DateTime) -> String {
+    t.fmt(DateTimeFormat::DateTime).unwrap_or_else(|_| t.secs().to_string())
+}
+
+pub async fn stat_object(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    json: bool,
+) -> Result<bool, aws_sdk_s3::Error> {
+    let head = match client.head_object().bucket(bucket).key(key).send().await {
+        Ok(h) => h,
+        Err(e) => {
+            let sdk_err: aws_sdk_s3::Error = e.into();
+            if let aws_sdk_s3::Error::NotFound(_) = sdk_err {
+                println!("Object 's3://{}/{}' not found.", bucket, key);
+                return Ok(false);
+            }
+            return Err(sdk_err);
+        }
+    };
+
+    let tagging = client
+        .get_object_tagging()
+        .bucket(bucket)
+        .key(key)
+        .set_version_id(head.version_id().map(|v| v.to_string()))
+        .send()
+        .await?;
+    // Sort metadata and tags so repeated runs diff cleanly.
+    let mut tags: Vec<(&str, &str)> = tagging.tag_set().iter().map(|t| (t.key(), t.value())).collect();
+    tags.sort();
+    let mut metadata: Vec<(&String, &String)> = head.metadata().map(|m| m.iter().collect()).unwrap_or_default();
+    metadata.sort();
+
+    let content_type = head.content_type();
+    let content_length = head.content_length();
+    let etag = head.e_tag();
+    let version_id = head.version_id();
+    let last_modified = head.last_modified().map(fmt_time);
+    // S3 omits the storage class header for STANDARD objects.
+    let storage_class = head.storage_class().map(|s| s.as_str()).unwrap_or("STANDARD");
+    let sse = head.server_side_encryption().map(|s| s.as_str());
+    let kms_key = head.ssekms_key_id();
+    let lock_mode = head.object_lock_mode().map(|m| m.as_str());
+    let retain_until = head.object_lock_retain_until_date().map(fmt_time);
+    let legal_hold = head.object_lock_legal_hold_status().map(|s| s.as_str());
+    let restore = head.restore();
+
+    if json {
+        let v = serde_json::json!({
+            "bucket": bucket,
+            "key": key,
+            "content_type": content_type,
+            "content_length": content_length,
+            "etag": etag,
+            "version_id": version_id,
+            "last_modified": last_modified,
+            "storage_class": storage_class,
+            "server_side_encryption": sse,
+            "ssekms_key_id": kms_key,
+            "metadata": metadata.iter().map(|(k, v)| ((*k).clone(), serde_json::Value::from(v.as_str()))).collect::<serde_json::Map<_, _>>(),
+            "tags": tags.iter().map(|(k, v)| (k.to_string(), serde_json::Value::from(*v))).collect::<serde_json::Map<_, _>>(),
+            "object_lock_mode": lock_mode,
+            "object_lock_retain_until": retain_until,
+            "object_lock_legal_hold": legal_hold,
+            "restore": restore,
+        });
+        println!("{:#}", v);
+        return Ok(true);
+    }
+
+    let or_dash = |v: Option<&str>| v.unwrap_or("-").to_string();
+    println!("s3://{}/{}", bucket, key);
+    println!("  ContentType:   {}", or_dash(content_type));
+    println!("  ContentLength: {}", content_length.map(|n| n.to_string()).unwrap_or_else(|| "-".into()));
+    println!("  ETag:          {}", or_dash(etag));
+    println!("  VersionId:     {}", or_dash(version_id));
+    println!("  LastModified:  {}", or_dash(last_modified.as_deref()));
+    println!("  StorageClass:  {}", storage_class);
+    match (sse, kms_key) {
+        (Some(s), Some(k)) => println!("  Encryption:    {} ({})", s, k),
+        (s, _) => println!("  Encryption:    {}", or_dash(s)),
+    }
+    println!("  ObjectLock:    {}", or_dash(lock_mode));
+    if let Some(r) = &retain_until {
+        println!("  RetainUntil:   {}", r);
+    }
+    if let Some(h) = legal_hold {
+        println!("  LegalHold:     {}", h);
+    }
+    println!("  Restore:       {}", or_dash(restore));
+    if !metadata.is_empty() {
+        println!("  Metadata:");
+        for (k, v) in &metadata {
+            println!("    {}: {}", k, v);
+        }
+    }
+    if !tags.is_empty() {
+        println!("  Tags:");
+        for (k, v) in &tags {
+            println!("    {}={}", k, v);
+        }
+    }
+    Ok(true)
+}
+
+/// Collect every object under `prefix` (all pages of ListObjectsV2).
+pub async fn list_objects_under(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<Object>, aws_sdk_s3::Error> {
+    let mut paginator = client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix(prefix)
+        .into_paginator()
+        .send();
+
+    let mut objects = Vec::new();
+    while let Some(result) = paginator.next().await {
+        let page = result?;
+        objects.extend(page.contents().iter().cloned());
+    }
+    Ok(objects)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresignMethod {
+    Get,
+    Put,
+}
+
+impl std::str::FromStr for PresignMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "get" => Ok(PresignMethod::Get),
+            "put" => Ok(PresignMethod::Put),
+            _ => Err(format!("unsupported presign method '{}' (use get or put)", s)),
+        }
+    }
+}
+
+/// Build a presigned GET or PUT request for a single object.
+///
+/// For PUT, `content_type` becomes part of the signature, so the uploader must
+/// send exactly that `Content-Type` header or S3 rejects the request.
+pub async fn presign_object(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    method: PresignMethod,
+    expires: Duration,
+    content_type: Option<&str>,
+) -> Result<PresignedRequest, Box<dyn std::error::Error + Send + Sync>> {
+    let config = PresigningConfig::expires_in(expires)?;
+    let req = match method {
+        PresignMethod::Get => {
+            client.get_object().bucket(bucket).key(key).presigned(config).await?
+        }
+        PresignMethod::Put => {
+            client
+                .put_object()
+                .bucket(bucket)
+                .key(key)
+                .set_content_type(content_type.map(|c| c.to_string()))
+                .presigned(config)
+                .await?
+        }
+    };
+    Ok(req)
+}
+
+/// Presign every object under `prefix`, writing `key<TAB>url` lines to `out`.
+pub async fn presign_prefix(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    method: PresignMethod,
+    expires: Duration,
+    content_type: Option<&str>,
+    out: &mut dyn Write,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let objects = list_objects_under(client, bucket, prefix).await?;
+    let mut count = 0usize;
+    for object in &objects {
+        let Some(key) = object.key() else { continue };
+        let req = presign_object(client, bucket, key, method, expires, content_type).await?;
+        writeln!(out, "{}\t{}", key, req.uri())?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Ordered include/exclude glob rules in the style of `aws s3 --include/--exclude`:
+/// everything matches by default and the last rule that matches a key decides.
+pub struct KeyFilter {
+    rules: Vec<(bool, glob::Pattern)>,
+    /// When set (`--keys-from`), only these keys are considered at all.
+    only: Option<HashSet<String>>,
+}
+
+impl KeyFilter {
+    pub fn new(rules: &[(bool, String)]) -> Result<Self, glob::PatternError> {
+        let rules = rules
+            .iter()
+            .map(|(include, p)| glob::Pattern::new(p).map(|pat| (*include, pat)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(KeyFilter { rules, only: None })
+    }
+
+    /// Restrict the filter to an explicit list of keys (e.g. the output of `s3 diff --keys`).
+    pub fn only_keys(mut self, keys: impl IntoIterator<Item = String>) -> Self {
+        self.only = Some(keys.into_iter().collect());
+        self
+    }
+
+    pub fn matches(&self, key: &str) -> bool {
+        if self.only.as_ref().is_some_and(|only| !only.contains(key)) {
+            return false;
+        }
+        let mut included = true;
+        for (include, pat) in &self.rules {
+            if pat.matches(key) {
+                included = *include;
+            }
+        }
+        included
+    }
+}
+
+/// Resolve the keys an rm/cp/mv operates on: the single key itself, or every
+/// key under the prefix when `recursive`. Filters see the key relative to the
+/// prefix in recursive mode and the full key otherwise.
+async fn select_keys(
+    client: &S3Client,
+    bucket: &str,
+    key_or_prefix: &str,
+    recursive: bool,
+    filter: &KeyFilter,
+) -> Result<Vec<(String, i64)>, aws_sdk_s3::Error> {
+    if !recursive {
+        if !filter.matches(key_or_prefix) {
+            return Ok(Vec::new());
+        }
+        let head = client.head_object().bucket(bucket).key(key_or_prefix).send().await?;
+        return Ok(vec![(key_or_prefix.to_string(), head.content_length().unwrap_or(0))]);
+    }
+    let objects = list_objects_under(client, bucket, key_or_prefix).await?;
+    Ok(objects
+        .iter()
+        .filter_map(|o| o.key().map(|k| (k.to_string(), o.size().unwrap_or(0))))
+        .filter(|(k, _)| filter.matches(&k[key_or_prefix.len()..]))
+        .collect())
+}
+
+/// Delete `keys` with DeleteObjects, 1000 keys per request. Per-key failures
+/// are reported on stderr; returns the number of keys deleted.
+pub async fn delete_keys(
+    client: &S3Client,
+    bucket: &str,
+    keys: &[String],
+    dry_run: bool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let targets: Vec<(String, Option<String>)> = keys.iter().map(|k| (k.clone(), None)).collect();
+    delete_versions(client, bucket, &targets, dry_run).await
+}
+
+/// Like `delete_keys` but each target may name a specific version (or delete
+/// marker) to remove permanently.
+pub async fn delete_versions(
+    client: &S3Client,
+    bucket: &str,
+    targets: &[(String, Option<String>)],
+    dry_run: bool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let describe = |key: &str, version: Option<&str>| match version {
+        Some(v) => format!("s3://{}/{} (version {})", bucket, key, v),
+        None => format!("s3://{}/{}", bucket, key),
+    };
+    if dry_run {
+        for (k, v) in targets {
+            println!("(dry run) delete: {}", describe(k, v.as_deref()));
+        }
+        return Ok(targets.len());
+    }
+
+    let mut deleted = 0usize;
+    for chunk in targets.chunks(DELETE_BATCH_SIZE) {
+        let ids = chunk
+            .iter()
+            .map(|(k, v)| ObjectIdentifier::builder().key(k).set_version_id(v.clone()).build())
+            .collect::<Result<Vec<_>, _>>()?;
+        let resp = client
+            .delete_objects()
+            .bucket(bucket)
+            .delete(Delete::builder().set_objects(Some(ids)).quiet(true).build()?)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+        // Quiet mode only reports failures, so everything else was deleted.
+        let failed: HashSet<(&str, Option<&str>)> = resp
+            .errors()
+            .iter()
+            .filter_map(|e| e.key().map(|k| (k, e.version_id())))
+            .collect();
+        for e in resp.errors() {
+            eprintln!(
+                "delete failed: {}: {} {}",
+                describe(e.key().unwrap_or("?"), e.version_id()),
+                e.code().unwrap_or(""),
+                e.message().unwrap_or("")
+            );
+        }
+        for (k, v) in chunk.iter().filter(|(k, v)| !failed.contains(&(k.as_str(), v.as_deref()))) {
+            println!("delete: {}", describe(k, v.as_deref()));
+            deleted += 1;
+        }
+    }
+    Ok(deleted)
+}
+
+pub async fn remove(
+    client: &S3Client,
+    bucket: &str,
+    key_or_prefix: &str,
+    recursive: bool,
+    filter: &KeyFilter,
+    dry_run: bool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let keys: Vec<String> = select_keys(client, bucket, key_or_prefix, recursive, filter)
+        .await?
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    delete_keys(client, bucket, &keys, dry_run).await
+}
+
+/// Percent-encode a `bucket/key` pair (plus optional `?versionId=`) for the
+/// CopySource header.
+fn copy_source(bucket: &str, key: &str, version_id: Option<&str>) -> String {
+    let mut out = format!("{}/", bucket);
+    for b in key.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => out.push(b as char),
+            _ => out.push_str(&format!("%{:02X}", b)),
+        }
+    }
+    if let Some(v) = version_id {
+        out.push_str("?versionId=");
+        out.push_str(v);
+    }
+    out
+}
+
+/// Server-side copy of one object. Objects larger than the 5 GiB CopyObject
+/// limit are copied with UploadPartCopy; multipart uploads don't inherit
+/// anything from the source, so content headers, metadata, storage class,
+/// SSE-S3/SSE-KMS settings and tags are carried over explicitly. ACLs, object
+/// lock settings and SSE-C keys are not.
+pub async fn copy_object(
+    client: &S3Client,
+    src_bucket: &str,
+    src_key: &str,
+    src_version: Option<&str>,
+    dst_bucket: &str,
+    dst_key: &str,
+    size: i64,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let source = copy_source(src_bucket, src_key, src_version);
+    if size <= MAX_COPY_OBJECT_SIZE {
+        client
+            .copy_object()
+            .copy_source(source)
+            .bucket(dst_bucket)
+            .key(dst_key)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+        return Ok(());
+    }
+
+    let head = client
+        .head_object()
+        .bucket(src_bucket)
+        .key(src_key)
+        .set_version_id(src_version.map(|v| v.to_string()))
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    let upload = client
+        .create_multipart_upload()
+        .bucket(dst_bucket)
+        .key(dst_key)
+        .set_content_type(head.content_type().map(|s| s.to_string()))
+        .set_cache_control(head.cache_control().map(|s| s.to_string()))
+        .set_content_disposition(head.content_disposition().map(|s| s.to_string()))
+        .set_content_encoding(head.content_encoding().map(|s| s.to_string()))
+        .set_content_language(head.content_language().map(|s| s.to_string()))
+        .set_website_redirect_location(head.website_redirect_location().map(|s| s.to_string()))
+        .set_metadata(head.metadata().cloned())
+        .set_storage_class(head.storage_class().cloned())
+        .set_server_side_encryption(head.server_side_encryption().cloned())
+        .set_ssekms_key_id(head.ssekms_key_id().map(|s| s.to_string()))
+        .set_bucket_key_enabled(head.bucket_key_enabled())
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    let upload_id = upload.upload_id().unwrap_or_default().to_string();
+
+    // S3 allows at most 10,000 parts per upload.
+    let part_size = MULTIPART_COPY_PART_SIZE.max((size + 9_999) / 10_000);
+    let result: Result<Vec<CompletedPart>, aws_sdk_s3::Error> = async {
+        let mut parts = Vec::new();
+        let mut start = 0i64;
+        let mut part_number = 1;
+        while start < size {
+            let end = (start + part_size).min(size) - 1;
+            let resp = client
+                .upload_part_copy()
+                .bucket(dst_bucket)
+                .key(dst_key)
+                .upload_id(&upload_id)
+                .part_number(part_number)
+                .copy_source(&source)
+                .copy_source_range(format!("bytes={}-{}", start, end))
+                .send()
+                .await?;
+            parts.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(resp.copy_part_result().and_then(|r| r.e_tag()).map(|s| s.to_string()))
+                    .build(),
+            );
+            start = end + 1;
+            part_number += 1;
+        }
+        Ok(parts)
+    }
+    .await;
+
+    let parts = match result {
+        Ok(p) => p,
+        Err(e) => {
+            let _ = client
+                .abort_multipart_upload()
+                .bucket(dst_bucket)
+                .key(dst_key)
+                .upload_id(&upload_id)
+                .send()
+                .await;
+            return Err(e.into());
+        }
+    };
+    client
+        .complete_multipart_upload()
+        .bucket(dst_bucket)
+        .key(dst_key)
+        .upload_id(&upload_id)
+        .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+
+    let tagging = client
+        .get_object_tagging()
+        .bucket(src_bucket)
+        .key(src_key)
+        .set_version_id(src_version.map(|v| v.to_string()))
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    if !tagging.tag_set().is_empty() {
+        client
+            .put_object_tagging()
+            .bucket(dst_bucket)
+            .key(dst_key)
+            .tagging(Tagging::builder().set_tag_set(Some(tagging.tag_set().to_vec())).build()?)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+    }
+    Ok(())
+}
+
+/// Copy (or move, when `delete_source`) one key or every key under a prefix.
+/// Moves only delete the source after a HeadObject on the destination confirms
+/// the copied size. Returns the number of objects copied.
+#[allow(clippy::too_many_arguments)]
+pub async fn copy(
+    client: &S3Client,
+    src_bucket: &str,
+    src: &str,
+    dst_bucket: &str,
+    dst: &str,
+    recursive: bool,
+    filter: &KeyFilter,
+    dry_run: bool,
+    delete_source: bool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let verb = if delete_source { "move" } else { "copy" };
+    // Every key would map onto itself; for a move the delete would then
+    // remove the only copy.
+    if src_bucket == dst_bucket && src == dst {
+        return Err(format!("cannot {} s3://{}/{} onto itself", verb, src_bucket, src).into());
+    }
+    let selected = select_keys(client, src_bucket, src, recursive, filter).await?;
+
+    let results = futures::stream::iter(selected.into_iter().map(|(src_key, size)| {
+        let dst_key = if recursive {
+            format!("{}{}", dst, &src_key[src.len()..])
+        } else {
+            dst.to_string()
+        };
+        async move {
+            if dry_run {
+                println!("(dry run) {}: s3://{}/{} to s3://{}/{}", verb, src_bucket, src_key, dst_bucket, dst_key);
+                return Ok(true);
+            }
+            copy_object(client, src_bucket, &src_key, None, dst_bucket, &dst_key, size).await?;
+            if delete_source {
+                let head = client.head_object().bucket(dst_bucket).key(&dst_key).send().await.map_err(aws_sdk_s3::Error::from)?;
+                if head.content_length() != Some(size) {
+                    eprintln!(
+                        "verify failed: s3://{}/{} has size {:?}, expected {}; source kept",
+                        dst_bucket,
+                        dst_key,
+                        head.content_length(),
+                        size
+                    );
+                    return Ok(false);
+                }
+                client.delete_object().bucket(src_bucket).key(&src_key).send().await.map_err(aws_sdk_s3::Error::from)?;
+            }
+            println!("{}: s3://{}/{} to s3://{}/{}", verb, src_bucket, src_key, dst_bucket, dst_key);
+            Ok::<bool, Box<dyn std::error::Error + Send + Sync>>(true)
+        }
+    }))
+    .buffer_unordered(COPY_CONCURRENCY)
+    .collect::<Vec<_>>()
+    .await;
+
+    let mut copied = 0usize;
+    for r in results {
+        if r? {
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
+
+/// One entry from ListObjectVersions: either an object version or a delete marker.
+pub struct VersionEntry {
+    pub key: String,
+    pub version_id: String,
+    pub is_latest: bool,
+    pub delete_marker: bool,
+    pub last_modified: Option<DateTime>,
+    pub size: Option<i64>,
+    pub etag: Option<String>,
+}
+
+/// Collect every version and delete marker under `prefix`, sorted by key and
+/// then newest first within each key.
+pub async fn list_versions(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<VersionEntry>, aws_sdk_s3::Error> {
+    let mut entries = Vec::new();
+    let mut key_marker: Option<String> = None;
+    let mut version_marker: Option<String> = None;
+    loop {
+        let page = client
+            .list_object_versions()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_key_marker(key_marker.take())
+            .set_version_id_marker(version_marker.take())
+            .send()
+            .await?;
+        for v in page.versions() {
+            entries.push(VersionEntry {
+                key: v.key().unwrap_or_default().to_string(),
+                version_id: v.version_id().unwrap_or("null").to_string(),
+                is_latest: v.is_latest().unwrap_or(false),
+                delete_marker: false,
+                last_modified: v.last_modified().cloned(),
+                size: v.size(),
+                etag: v.e_tag().map(|s| s.to_string()),
+            });
+        }
+        for m in page.delete_markers() {
+            entries.push(VersionEntry {
+                key: m.key().unwrap_or_default().to_string(),
+                version_id: m.version_id().unwrap_or("null").to_string(),
+                is_latest: m.is_latest().unwrap_or(false),
+                delete_marker: true,
+                last_modified: m.last_modified().cloned(),
+                size: None,
+                etag: None,
+            });
+        }
+        if !page.is_truncated().unwrap_or(false) {
+            break;
+        }
+        key_marker = page.next_key_marker().map(|s| s.to_string());
+        version_marker = page.next_version_id_marker().map(|s| s.to_string());
+    }
+
+    entries.sort_by(|a, b| {
+        a.key
+            .cmp(&b.key)
+            .then_with(|| b.is_latest.cmp(&a.is_latest))
+            .then_with(|| b.last_modified.map(|t| t.as_nanos()).cmp(&a.last_modified.map(|t| t.as_nanos())))
+    });
+    Ok(entries)
+}
+
+pub async fn print_versions(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    json: bool,
+) -> Result<usize, aws_sdk_s3::Error> {
+    let entries = list_versions(client, bucket, prefix).await?;
+    if json {
+        let rows: Vec<serde_json::Value> = entries
+            .iter()
+            .map(|e| {
+                serde_json::json!({
+                    "key": e.key,
+                    "version_id": e.version_id,
+                    "is_latest": e.is_latest,
+                    "delete_marker": e.delete_marker,
+                    "last_modified": e.last_modified.as_ref().map(fmt_time),
+                    "size": e.size,
+                    "etag": e.etag,
+                })
+            })
+            .collect();
+        println!("{:#}", serde_json::Value::Array(rows));
+        return Ok(entries.len());
+    }
+    for e in &entries {
+        let kind = if e.delete_marker {
+            "DELETE MARKER".to_string()
+        } else {
+            e.size.map(|n| n.to_string()).unwrap_or_default()
+        };
+        println!(
+            "{}\t{}\t{}\t{}\t{}",
+            e.key,
+            e.version_id,
+            if e.is_latest { "latest" } else { "-" },
+            e.last_modified.as_ref().map(fmt_time).unwrap_or_default(),
+            kind
+        );
+    }
+    Ok(entries.len())
+}
+
+/// Make an older version current again by copying it over the key.
+pub async fn restore_version(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    version_id: &str,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let head = client
+        .head_object()
+        .bucket(bucket)
+        .key(key)
+        .version_id(version_id)
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    copy_object(client, bucket, key, Some(version_id), bucket, key, head.content_length().unwrap_or(0)).await
+}
+
+/// Remove the delete marker hiding `key`, if the latest version is one.
+/// Returns false when the key is not currently deleted.
+pub async fn undelete(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+) -> Result<bool, aws_sdk_s3::Error> {
+    let entries = list_versions(client, bucket, key).await?;
+    let marker = entries.iter().find(|e| e.key == key && e.is_latest && e.delete_marker);
+    let Some(marker) = marker else {
+        return Ok(false);
+    };
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(key)
+        .version_id(&marker.version_id)
+        .send()
+        .await?;
+    Ok(true)
+}
+
+/// Permanently delete noncurrent versions and delete markers under `prefix`
+/// that have been noncurrent for longer than `older_than`. As with lifecycle
+/// rules, a version becomes noncurrent when its successor is written.
+pub async fn purge_versions(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    older_than: Duration,
+    dry_run: bool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let entries = list_versions(client, bucket, prefix).await?;
+    let cutoff = DateTime::from(std::time::SystemTime::now() - older_than);
+
+    let mut targets: Vec<(String, Option<String>)> = Vec::new();
+    for pair in entries.windows(2) {
+        let (newer, older) = (&pair[0], &pair[1]);
+        if newer.key != older.key || older.is_latest {
+            continue;
+        }
+        let noncurrent_since = newer.last_modified.map(|t| t.as_nanos());
+        if noncurrent_since.is_some_and(|t| t < cutoff.as_nanos()) {
+            targets.push((older.key.clone(), Some(older.version_id.clone())));
+        }
+    }
+    delete_versions(client, bucket, &targets, dry_run).await
+}
+
+/// Collect every in-progress multipart upload in `bucket` under `prefix`.
+pub async fn list_multipart_uploads(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+) -> Result<Vec<MultipartUpload>, aws_sdk_s3::Error> {
+    let mut uploads = Vec::new();
+    let mut key_marker: Option<String> = None;
+    let mut upload_id_marker: Option<String> = None;
+    loop {
+        let page = client
+            .list_multipart_uploads()
+            .bucket(bucket)
+            .prefix(prefix)
+            .set_key_marker(key_marker.take())
+            .set_upload_id_marker(upload_id_marker.take())
+            .send()
+            .await?;
+        uploads.extend(page.uploads().iter().cloned());
+        if !page.is_truncated().unwrap_or(false) {
+            break;
+        }
+        key_marker = page.next_key_marker().map(|s| s.to_string());
+        upload_id_marker = page.next_upload_id_marker().map(|s| s.to_string());
+    }
+    Ok(uploads)
+}
+
+/// Clone `client` with its region replaced, for calls that must go to the
+/// endpoint of the region a bucket lives in.
+fn regional_client(client: &S3Client, region: &str) -> S3Client {
+    S3Client::from_conf(client.config().to_builder().region(Region::new(region.to_string())).build())
+}
+
+/// Create a bucket. Outside us-east-1 S3 requires an explicit location
+/// constraint, so one is sent whenever the target region differs.
+pub async fn make_bucket(
+    client: &S3Client,
+    bucket: &str,
+    region: Option<&str>,
+) -> Result<(), aws_sdk_s3::Error> {
+    let region = region
+        .map(|r| r.to_string())
+        .or_else(|| client.config().region().map(|r| r.to_string()))
+        .unwrap_or_else(|| "us-east-1".to_string());
+    let mut req = regional_client(client, &region).create_bucket().bucket(bucket);
+    if region != "us-east-1" {
+        req = req.create_bucket_configuration(
+            CreateBucketConfiguration::builder()
+                .location_constraint(BucketLocationConstraint::from(region.as_str()))
+                .build(),
+        );
+    }
+    req.send().await?;
+    Ok(())
+}
+
+/// Delete every object version, delete marker and in-progress multipart
+/// upload in `bucket`, leaving it empty. Returns (versions deleted, uploads aborted).
+pub async fn empty_bucket(
+    client: &S3Client,
+    bucket: &str,
+) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+    let mut aborted = 0usize;
+    for upload in list_multipart_uploads(client, bucket, "").await? {
+        client
+            .abort_multipart_upload()
+            .bucket(bucket)
+            .set_key(upload.key().map(|s| s.to_string()))
+            .set_upload_id(upload.upload_id().map(|s| s.to_string()))
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+        aborted += 1;
+    }
+
+    // ListObjectVersions also returns objects in unversioned buckets (with
+    // version id "null"), so one pass covers both cases.
+    let targets: Vec<(String, Option<String>)> = list_versions(client, bucket, "")
+        .await?
+        .into_iter()
+        .map(|e| (e.key, Some(e.version_id)))
+        .collect();
+    let deleted = delete_versions(client, bucket, &targets, false).await?;
+    if deleted != targets.len() {
+        return Err(format!("{} object version(s) could not be deleted", targets.len() - deleted).into());
+    }
+    Ok((deleted, aborted))
+}
+
+pub async fn remove_bucket(client: &S3Client, bucket: &str) -> Result<(), aws_sdk_s3::Error> {
+    client.delete_bucket().bucket(bucket).send().await?;
+    Ok(())
+}
+
+/// Per-bucket configuration summary shown by `list-buckets --long`.
+struct BucketSummary {
+    name: String,
+    created: Option<String>,
+    region: Option<String>,
+    versioning: Option<String>,
+    encryption: Option<String>,
+    public_access_block: Option<String>,
+    tags: Vec<(String, String)>,
+    errors: Vec<String>,
+}
+
+/// Error codes S3 returns when a bucket simply has no such configuration.
+pub fn is_missing_config(err: &aws_sdk_s3::Error) -> bool {
+    matches!(
+        err.code(),
+        Some(
+            "ServerSideEncryptionConfigurationNotFoundError"
+                | "NoSuchPublicAccessBlockConfiguration"
+                | "NoSuchTagSet"
+                | "NoSuchBucketPolicy"
+                | "NoSuchLifecycleConfiguration"
+                | "NoSuchCORSConfiguration"
+        )
+    )
+}
+
+async fn summarize_bucket(client: &S3Client, name: &str, created: Option<String>) -> BucketSummary {
+    let mut summary = BucketSummary {
+        name: name.to_string(),
+        created,
+        region: None,
+        versioning: None,
+        encryption: None,
+        public_access_block: None,
+        tags: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    // An empty LocationConstraint means us-east-1.
+    match client.get_bucket_location().bucket(name).send().await {
+        Ok(loc) => {
+            let region = loc
+                .location_constraint()
+                .map(|c| c.as_str())
+                .filter(|c| !c.is_empty())
+                .unwrap_or("us-east-1");
+            summary.region = Some(region.to_string());
+        }
+        Err(e) => summary.errors.push(format!("location: {}", DisplayErrorContext(aws_sdk_s3::Error::from(e)))),
+    }
+    let client = match &summary.region {
+        Some(r) => regional_client(client, r),
+        None => client.clone(),
+    };
+
+    let (versioning, encryption, pab, tagging) = futures::join!(
+        client.get_bucket_versioning().bucket(name).send(),
+        client.get_bucket_encryption().bucket(name).send(),
+        client.get_public_access_block().bucket(name).send(),
+        client.get_bucket_tagging().bucket(name).send(),
+    );
+
+    match versioning {
+        // Buckets that never had versioning enabled report no status at all.
+        Ok(v) => summary.versioning = Some(v.status().map(|s| s.as_str()).unwrap_or("Disabled").to_string()),
+        Err(e) => summary.errors.push(format!("versioning: {}", DisplayErrorContext(aws_sdk_s3::Error::from(e)))),
+    }
+    match encryption.map_err(aws_sdk_s3::Error::from) {
+        Ok(enc) => {
+            let algs: Vec<String> = enc
+                .server_side_encryption_configuration()
+                .map(|c| c.rules())
+                .unwrap_or_default()
+                .iter()
+                .filter_map(|r| r.apply_server_side_encryption_by_default())
+                .map(|d| match d.kms_master_key_id() {
+                    Some(k) => format!("{} ({})", d.sse_algorithm().as_str(), k),
+                    None => d.sse_algorithm().as_str().to_string(),
+                })
+                .collect();
+            summary.encryption = Some(if algs.is_empty() { "none".to_string() } else { algs.join(",") });
+        }
+        Err(e) if is_missing_config(&e) => summary.encryption = Some("none".to_string()),
+        Err(e) => summary.errors.push(format!("encryption: {}", DisplayErrorContext(&e))),
+    }
+    match pab.map_err(aws_sdk_s3::Error::from) {
+        Ok(p) => {
+            let cfg = p.public_access_block_configuration();
+            let flags = [
+                ("BlockPublicAcls", cfg.and_then(|c| c.block_public_acls())),
+                ("IgnorePublicAcls", cfg.and_then(|c| c.ignore_public_acls())),
+                ("BlockPublicPolicy", cfg.and_then(|c| c.block_public_policy())),
+                ("RestrictPublicBuckets", cfg.and_then(|c| c.restrict_public_buckets())),
+            ];
+            let on: Vec<&str> = flags.iter().filter(|(_, v)| *v == Some(true)).map(|(n, _)| *n).collect();
+            summary.public_access_block = Some(match on.len() {
+                4 => "all".to_string(),
+                0 => "none".to_string(),
+                _ => on.join(","),
+            });
+        }
+        Err(e) if is_missing_config(&e) => summary.public_access_block = Some("none".to_string()),
+        Err(e) => summary.errors.push(format!("public access block: {}", DisplayErrorContext(&e))),
+    }
+    match tagging.map_err(aws_sdk_s3::Error::from) {
+        Ok(t) => {
+            summary.tags = t.tag_set().iter().map(|t| (t.key().to_string(), t.value().to_string())).collect();
+            summary.tags.sort();
+        }
+        Err(e) if is_missing_config(&e) => {}
+        Err(e) => summary.errors.push(format!("tags: {}", DisplayErrorContext(&e))),
+    }
+    summary
+}
+
+/// `list-buckets --long`: creation date, region and configuration summary for
+/// every bucket, fetched concurrently. Lookups that fail (e.g. AccessDenied)
+/// are reported per bucket instead of aborting the listing.
+pub async fn list_s3_buckets_long(client: &S3Client, json: bool) -> Result<usize, aws_sdk_s3::Error> {
+    let resp = client.list_buckets().send().await?;
+    let buckets: Vec<(String, Option<String>)> = resp
+        .buckets()
+        .iter()
+        .map(|b| (b.name().unwrap_or("(no name)").to_string(), b.creation_date().map(fmt_time)))
+        .collect();
+
+    let summaries: Vec<BucketSummary> = futures::stream::iter(buckets)
+        .map(|(name, created)| async move { summarize_bucket(client, &name, created).await })
+        .buffered(BUCKET_SUMMARY_CONCURRENCY)
+        .collect()
+        .await;
+
+    if json {
+        let rows: Vec<serde_json::Value> = summaries
+            .iter()
+            .map(|b| {
+                serde_json::json!({
+                    "name": b.name,
+                    "creation_date": b.created,
+                    "region": b.region,
+                    "versioning": b.versioning,
+                    "encryption": b.encryption,
+                    "public_access_block": b.public_access_block,
+                    "tags": b.tags.iter().map(|(k, v)| (k.clone(), serde_json::Value::from(v.as_str()))).collect::<serde_json::Map<_, _>>(),
+                    "errors": b.errors,
+                })
+            })
+            .collect();
+        println!("{:#}", serde_json::Value::Array(rows));
+        return Ok(summaries.len());
+    }
+
+    if summaries.is_empty() {
+        println!("No S3 buckets found.");
+        return Ok(0);
+    }
+    let dash = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+    println!("NAME\tCREATED\tREGION\tVERSIONING\tENCRYPTION\tPUBLIC_ACCESS_BLOCK\tTAGS");
+    for b in &summaries {
+        let tags = b.tags.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(",");
+        println!(
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            b.name,
+            dash(&b.created),
+            dash(&b.region),
+            dash(&b.versioning),
+            dash(&b.encryption),
+            dash(&b.public_access_block),
+            if tags.is_empty() { "-".to_string() } else { tags }
+        );
+        for e in &b.errors {
+            eprintln!("{}: {}", b.name, e);
+        }
+    }
+    Ok(summaries.len())
+}
+
+pub async fn get_tags(client: &S3Client, bucket: &str, key: &str) -> Result<Vec<(String, String)>, aws_sdk_s3::Error> {
+    let resp = client.get_object_tagging().bucket(bucket).key(key).send().await?;
+    let mut tags: Vec<(String, String)> = resp.tag_set().iter().map(|t| (t.key().to_string(), t.value().to_string())).collect();
+    tags.sort();
+    Ok(tags)
+}
+
+pub async fn print_tags(client: &S3Client, bucket: &str, key: &str, json: bool) -> Result<(), aws_sdk_s3::Error> {
+    let tags = get_tags(client, bucket, key).await?;
+    if json {
+        let map: serde_json::Map<String, serde_json::Value> = tags.into_iter().map(|(k, v)| (k, v.into())).collect();
+        println!("{:#}", serde_json::Value::Object(map));
+    } else {
+        for (k, v) in tags {
+            println!("{}={}", k, v);
+        }
+    }
+    Ok(())
+}
+
+/// A change to an object's tag set.
+pub enum TagOp {
+    /// Add or overwrite these tags; with `replace`, drop every other tag.
+    Set { tags: Vec<(String, String)>, replace: bool },
+    /// Remove these tag keys, or every tag when empty.
+    Delete { keys: Vec<String> },
+}
+
+/// Apply `op` to one object. PutObjectTagging replaces the whole tag set, so
+/// merges read the current tags first.
+pub async fn apply_tags(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    op: &TagOp,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let new_tags: Vec<(String, String)> = match op {
+        TagOp::Set { tags, replace: true } => tags.clone(),
+        TagOp::Set { tags, replace: false } => {
+            let mut current = get_tags(client, bucket, key).await?;
+            current.retain(|(k, _)| !tags.iter().any(|(nk, _)| nk == k));
+            current.extend(tags.iter().cloned());
+            current
+        }
+        TagOp::Delete { keys } if keys.is_empty() => Vec::new(),
+        TagOp::Delete { keys } => {
+            let mut current = get_tags(client, bucket, key).await?;
+            current.retain(|(k, _)| !keys.contains(k));
+            current
+        }
+    };
+
+    if new_tags.is_empty() {
+        client
+            .delete_object_tagging()
+            .bucket(bucket)
+            .key(key)
+            .send()
+            .await
+            .map_err(aws_sdk_s3::Error::from)?;
+        return Ok(());
+    }
+    let tag_set = new_tags
+        .iter()
+        .map(|(k, v)| Tag::builder().key(k).value(v).build())
+        .collect::<Result<Vec<_>, _>>()?;
+    client
+        .put_object_tagging()
+        .bucket(bucket)
+        .key(key)
+        .tagging(Tagging::builder().set_tag_set(Some(tag_set)).build()?)
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    Ok(())
+}
+
+/// Clone `client` with a larger retry budget for bulk per-object operations.
+pub fn bulk_client(client: &S3Client) -> S3Client {
+    S3Client::from_conf(
+        client
+            .config()
+            .to_builder()
+            .retry_config(RetryConfig::standard().with_max_attempts(BULK_MAX_ATTEMPTS))
+            .build(),
+    )
+}
+
+/// Apply `op` to every object under `prefix` that passes `filter`, several at
+/// a time. Throttling is absorbed by a client with a larger retry budget;
+/// objects that still fail are reported and counted. Returns (ok, failed).
+pub async fn apply_tags_bulk(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    filter: &ListFilter,
+    op: &TagOp,
+    dry_run: bool,
+) -> Result<(usize, usize), aws_sdk_s3::Error> {
+    let keys: Vec<String> = list_objects_under(client, bucket, filter.effective_prefix(prefix))
+        .await?
+        .iter()
+        .filter(|o| filter.matches(o))
+        .filter_map(|o| o.key().map(|k| k.to_string()))
+        .collect();
+    if dry_run {
+        for k in &keys {
+            println!("(dry run) tag: s3://{}/{}", bucket, k);
+        }
+        return Ok((keys.len(), 0));
+    }
+
+    let client = &bulk_client(client);
+    let results: Vec<bool> = futures::stream::iter(keys)
+        .map(|key| async move {
+            match apply_tags(client, bucket, &key, op).await {
+                Ok(()) => {
+                    println!("tag: s3://{}/{}", bucket, key);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("tag failed: s3://{}/{}: {}", bucket, key, e);
+                    false
+                }
+            }
+        })
+        .buffer_unordered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+    let ok = results.iter().filter(|r| **r).count();
+    Ok((ok, results.len() - ok))
+}
+
+/// Storage classes whose objects must be restored before they can be read.
+fn is_archive_class(class: &str) -> bool {
+    matches!(class, "GLACIER" | "DEEP_ARCHIVE")
+}
+
+/// Keys to act on for restore commands: the key itself, or every object under
+/// the prefix that passes `filter`. With `archived_only`, objects that are not
+/// in an archive storage class are dropped; INTELLIGENT_TIERING objects are
+/// kept only if HeadObject reports them in one of the archive access tiers.
+async fn restore_candidates(
+    client: &S3Client,
+    bucket: &str,
+    key_or_prefix: &str,
+    recursive: bool,
+    filter: &ListFilter,
+    archived_only: bool,
+) -> Result<Vec<String>, aws_sdk_s3::Error> {
+    if !recursive {
+        if archived_only {
+            let head = client.head_object().bucket(bucket).key(key_or_prefix).send().await?;
+            let archived = head.storage_class().is_some_and(|c| is_archive_class(c.as_str())) || head.archive_status().is_some();
+            if !archived {
+                eprintln!("skip: s3://{}/{} is not in an archive storage class", bucket, key_or_prefix);
+                return Ok(Vec::new());
+            }
+        }
+        return Ok(vec![key_or_prefix.to_string()]);
+    }
+    let objects = list_objects_under(client, bucket, filter.effective_prefix(key_or_prefix)).await?;
+    let selected = objects.iter().filter(|o| filter.matches(o)).filter_map(|o| o.key().map(|k| (k, o.storage_class())));
+    if !archived_only {
+        return Ok(selected.map(|(k, _)| k.to_string()).collect());
+    }
+    let client = &bulk_client(client);
+    let keys: Vec<Option<String>> = futures::stream::iter(selected)
+        .map(|(key, class)| async move {
+            match class.map(|c| c.as_str()) {
+                Some(c) if is_archive_class(c) => Ok(Some(key.to_string())),
+                Some("INTELLIGENT_TIERING") => {
+                    let head = client.head_object().bucket(bucket).key(key).send().await?;
+                    Ok(head.archive_status().is_some().then(|| key.to_string()))
+                }
+                _ => Ok(None),
+            }
+        })
+        .buffered(BULK_CONCURRENCY)
+        .collect::<Vec<Result<_, aws_sdk_s3::Error>>>()
+        .await
+        .into_iter()
+        .collect::<Result<_, _>>()?;
+    Ok(keys.into_iter().flatten().collect())
+}
+
+pub fn parse_tier(s: &str) -> Result<Tier, String> {
+    match s.to_ascii_lowercase().as_str() {
+        "bulk" => Ok(Tier::Bulk),
+        "standard" => Ok(Tier::Standard),
+        "expedited" => Ok(Tier::Expedited),
+        _ => Err(format!("unknown restore tier '{}' (use bulk, standard or expedited)", s)),
+    }
+}
+
+/// Issue RestoreObject for every archived object selected. Returns
+/// (requested, already in progress, failed).
+#[allow(clippy::too_many_arguments)]
+pub async fn restore_objects(
+    client: &S3Client,
+    bucket: &str,
+    key_or_prefix: &str,
+    recursive: bool,
+    filter: &ListFilter,
+    days: i32,
+    tier: Tier,
+    dry_run: bool,
+) -> Result<(usize, usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+    let keys = restore_candidates(client, bucket, key_or_prefix, recursive, filter, true).await?;
+    if dry_run {
+        for k in &keys {
+            println!("(dry run) restore: s3://{}/{} ({} days, {})", bucket, k, days, tier.as_str());
+        }
+        return Ok((keys.len(), 0, 0));
+    }
+
+    let request = RestoreRequest::builder()
+        .days(days)
+        .glacier_job_parameters(GlacierJobParameters::builder().tier(tier.clone()).build()?)
+        .build();
+    let client = &bulk_client(client);
+    let request = &request;
+    enum Outcome {
+        Requested,
+        InProgress,
+        Failed,
+    }
+    let results: Vec<Outcome> = futures::stream::iter(keys)
+        .map(|key| async move {
+            let res = client
+                .restore_object()
+                .bucket(bucket)
+                .key(&key)
+                .restore_request(request.clone())
+                .send()
+                .await
+                .map_err(aws_sdk_s3::Error::from);
+            match res {
+                Ok(_) => {
+                    println!("restore: s3://{}/{}", bucket, key);
+                    Outcome::Requested
+                }
+                Err(e) if e.code() == Some("RestoreAlreadyInProgress") => {
+                    println!("in progress: s3://{}/{}", bucket, key);
+                    Outcome::InProgress
+                }
+                Err(e) => {
+                    eprintln!("restore failed: s3://{}/{}: {}", bucket, key, DisplayErrorContext(&e));
+                    Outcome::Failed
+                }
+            }
+        })
+        .buffer_unordered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+
+    let requested = results.iter().filter(|r| matches!(r, Outcome::Requested)).count();
+    let in_progress = results.iter().filter(|r| matches!(r, Outcome::InProgress)).count();
+    let failed = results.iter().filter(|r| matches!(r, Outcome::Failed)).count();
+    Ok((requested, in_progress, failed))
+}
+
+/// Restore state of one object, from its storage class and `x-amz-restore` header.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RestoreState {
+    NotArchived,
+    Archived,
+    InProgress,
+    Available,
+    Expired,
+}
+
+impl RestoreState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RestoreState::NotArchived => "not-archived",
+            RestoreState::Archived => "archived",
+            RestoreState::InProgress => "in-progress",
+            RestoreState::Available => "available",
+            RestoreState::Expired => "expired",
+        }
+    }
+}
+
+/// Interpret a `x-amz-restore` header such as
+/// `ongoing-request="false", expiry-date="Fri, 21 Dec 2012 00:00:00 GMT"`.
+fn parse_restore_header(header: Option<&str>, archived: bool) -> (RestoreState, Option<DateTime>) {
+    let Some(h) = header else {
+        let state = if archived { RestoreState::Archived } else { RestoreState::NotArchived };
+        return (state, None);
+    };
+    if h.contains("ongoing-request=\"true\"") {
+        return (RestoreState::InProgress, None);
+    }
+    let expiry = h
+        .split_once("expiry-date=\"")
+        .and_then(|(_, rest)| rest.split_once('"'))
+        .and_then(|(date, _)| DateTime::from_str(date, DateTimeFormat::HttpDate).ok());
+    let now = DateTime::from(std::time::SystemTime::now());
+    match expiry {
+        Some(e) if e.as_nanos() <= now.as_nanos() => (RestoreState::Expired, Some(e)),
+        _ => (RestoreState::Available, expiry),
+    }
+}
+
+/// Report the restore state of the selected objects. Returns the number of
+/// restores still in progress so callers can poll until it reaches zero.
+pub async fn restore_status(
+    client: &S3Client,
+    bucket: &str,
+    key_or_prefix: &str,
+    recursive: bool,
+    filter: &ListFilter,
+    json: bool,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let keys = restore_candidates(client, bucket, key_or_prefix, recursive, filter, false).await?;
+    let client = &bulk_client(client);
+    let mut rows: Vec<(String, RestoreState, Option<DateTime>)> = futures::stream::iter(keys)
+        .map(|key| async move {
+            let head = client.head_object().bucket(bucket).key(&key).send().await.map_err(aws_sdk_s3::Error::from)?;
+            let archived = head.storage_class().is_some_and(|c| is_archive_class(c.as_str())) || head.archive_status().is_some();
+            let (state, expiry) = parse_restore_header(head.restore(), archived);
+            Ok::<_, aws_sdk_s3::Error>((key, state, expiry))
+        })
+        .buffer_unordered(BULK_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<_, _>>()?;
+    rows.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if json {
+        let out: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|(k, state, expiry)| {
+                serde_json::json!({ "key": k, "state": state.as_str(), "expiry": expiry.as_ref().map(fmt_time) })
+            })
+            .collect();
+        println!("{:#}", serde_json::Value::Array(out));
+    } else {
+        for (k, state, expiry) in &rows {
+            match expiry {
+                Some(e) => println!("{}\t{}\t{}", k, state.as_str(), fmt_time(e)),
+                None => println!("{}\t{}", k, state.as_str()),
+            }
+        }
+        let count = |s: RestoreState| rows.iter().filter(|r| r.1 == s).count();
+        eprintln!(
+            "\n{} in progress, {} available, {} expired, {} archived (not restored), {} not archived",
+            count(RestoreState::InProgress),
+            count(RestoreState::Available),
+            count(RestoreState::Expired),
+            count(RestoreState::Archived),
+            count(RestoreState::NotArchived)
+        );
+    }
+    Ok(rows.iter().filter(|r| r.1 == RestoreState::InProgress).count())
+}
+
+/// Part count and total bytes uploaded so far for one multipart upload.
+async fn upload_progress(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    upload_id: &str,
+) -> Result<(usize, i64), aws_sdk_s3::Error> {
+    let mut paginator = client
+        .list_parts()
+        .bucket(bucket)
+        .key(key)
+        .upload_id(upload_id)
+        .into_paginator()
+        .send();
+    let (mut parts, mut bytes) = (0usize, 0i64);
+    while let Some(page) = paginator.next().await {
+        for p in page?.parts() {
+            parts += 1;
+            bytes += p.size().unwrap_or(0);
+        }
+    }
+    Ok((parts, bytes))
+}
+
+struct UploadRow {
+    key: String,
+    upload_id: String,
+    initiated: Option<String>,
+    /// (part count, bytes), when ListParts succeeded
+    progress: Option<(usize, i64)>,
+}
+
+/// `s3 multipart list`: in-progress uploads with initiation time, part count
+/// and bytes uploaded (these bytes are billed until the upload is aborted).
+pub async fn print_multipart_uploads(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    json: bool,
+) -> Result<usize, aws_sdk_s3::Error> {
+    let uploads = list_multipart_uploads(client, bucket, prefix).await?;
+    let client = &bulk_client(client);
+    // Part listings are best-effort: one unreadable upload shouldn't hide the rest.
+    let rows: Vec<UploadRow> = futures::stream::iter(uploads)
+        .map(|u| async move {
+            let key = u.key().unwrap_or_default().to_string();
+            let upload_id = u.upload_id().unwrap_or_default().to_string();
+            let progress = match upload_progress(client, bucket, &key, &upload_id).await {
+                Ok(p) => Some(p),
+                Err(e) => {
+                    eprintln!("list parts failed: s3://{}/{} ({}): {}", bucket, key, upload_id, DisplayErrorContext(&e));
+                    None
+                }
+            };
+            UploadRow { initiated: u.initiated().map(fmt_time), key, upload_id, progress }
+        })
+        .buffered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+
+    if json {
+        let out: Vec<serde_json::Value> = rows
+            .iter()
+            .map(|r| {
+                serde_json::json!({
+                    "key": r.key,
+                    "upload_id": r.upload_id,
+                    "initiated": r.initiated,
+                    "parts": r.progress.map(|p| p.0),
+                    "bytes": r.progress.map(|p| p.1),
+                })
+            })
+            .collect();
+        println!("{:#}", serde_json::Value::Array(out));
+        return Ok(rows.len());
+    }
+    for r in &rows {
+        let (parts, bytes) = match r.progress {
+            Some((p, b)) => (p.to_string(), b.to_string()),
+            None => ("-".to_string(), "-".to_string()),
+        };
+        println!("{}\t{}\t{}\t{} part(s)\t{} bytes", r.key, r.upload_id, r.initiated.as_deref().unwrap_or("-"), parts, bytes);
+    }
+    let total: i64 = rows.iter().filter_map(|r| r.progress).map(|p| p.1).sum();
+    if !rows.is_empty() {
+        println!("\n{} bytes held by incomplete uploads", total);
+    }
+    Ok(rows.len())
+}
+
+/// Abort in-progress multipart uploads under `prefix` that were initiated
+/// more than `older_than` ago. Returns the number aborted.
+pub async fn abort_multipart_uploads(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    older_than: Duration,
+    dry_run: bool,
+) -> Result<usize, aws_sdk_s3::Error> {
+    let cutoff = DateTime::from(std::time::SystemTime::now() - older_than);
+    let stale: Vec<MultipartUpload> = list_multipart_uploads(client, bucket, prefix)
+        .await?
+        .into_iter()
+        .filter(|u| u.initiated().is_some_and(|t| t.as_nanos() < cutoff.as_nanos()))
+        .collect();
+    if dry_run {
+        for u in &stale {
+            println!("(dry run) abort: s3://{}/{} ({})", bucket, u.key().unwrap_or_default(), u.upload_id().unwrap_or_default());
+        }
+        return Ok(stale.len());
+    }
+
+    let client = &bulk_client(client);
+    let results: Vec<bool> = futures::stream::iter(stale)
+        .map(|u| async move {
+            let key = u.key().unwrap_or_default();
+            let upload_id = u.upload_id().unwrap_or_default();
+            match client.abort_multipart_upload().bucket(bucket).key(key).upload_id(upload_id).send().await {
+                Ok(_) => {
+                    println!("abort: s3://{}/{} ({})", bucket, key, upload_id);
+                    true
+                }
+                Err(e) => {
+                    eprintln!("abort failed: s3://{}/{} ({}): {}", bucket, key, upload_id, DisplayErrorContext(aws_sdk_s3::Error::from(e)));
+                    false
+                }
+            }
+        })
+        .buffer_unordered(BULK_CONCURRENCY)
+        .collect()
+        .await;
+    Ok(results.iter().filter(|r| **r).count())
+}
+
+/// Fetch at most `max_bytes` of an object (ranged GET) and return the regex
+/// matches as `key:line:text` strings. Gzip content is detected by its magic
+/// bytes and decompressed; a body cut short by the cap simply ends the scan.
+async fn grep_object(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    re: &regex::Regex,
+    max_bytes: i64,
+) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let resp = client
+        .get_object()
+        .bucket(bucket)
+        .key(key)
+        .range(format!("bytes=0-{}", max_bytes.max(1) - 1))
+        .send()
+        .await
+        .map_err(aws_sdk_s3::Error::from)?;
+    let data = resp.body.collect().await?.into_bytes();
+
+    let mut reader: Box<dyn BufRead + Send> = if data.starts_with(&[0x1f, 0x8b]) {
+        Box::new(std::io::BufReader::new(flate2::read::MultiGzDecoder::new(&data[..])))
+    } else {
+        Box::new(&data[..])
+    };
+    let mut matches = Vec::new();
+    let mut buf = Vec::new();
+    let mut line_no = 0usize;
+    loop {
+        buf.clear();
+        match reader.read_until(b'\n', &mut buf) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+        line_no += 1;
+        let line = String::from_utf8_lossy(&buf);
+        let line = line.trim_end_matches(['\n', '\r']);
+        if re.is_match(line) {
+            matches.push(format!("{}:{}:{}", key, line_no, line));
+        }
+    }
+    Ok(matches)
+}
+
+/// `s3 grep`: search every object under `prefix` (narrowed by `filter`) for
+/// lines matching `re`, several objects at a time. Results are printed in key
+/// order; returns the number of matching lines.
+pub async fn grep_objects(
+    client: &S3Client,
+    bucket: &str,
+    prefix: &str,
+    filter: &ListFilter,
+    re: &regex::Regex,
+    max_bytes: i64,
+) -> Result<usize, aws_sdk_s3::Error> {
+    let keys: Vec<String> = list_objects_under(client, bucket, filter.effective_prefix(prefix))
+        .await?
+        .iter()
+        .filter(|o| filter.matches(o))
+        // A ranged GET of an empty object fails with InvalidRange; nothing to search anyway.
+        .filter(|o| o.size().unwrap_or(0) > 0)
+        .filter_map(|o| o.key().map(|k| k.to_string()))
+        .collect();
+
+    let client = &bulk_client(client);
+    let mut results = futures::stream::iter(keys)
+        .map(|key| async move {
+            let res = grep_object(client, bucket, &key, re, max_bytes).await;
+            (key, res)
+        })
+        .buffered(BULK_CONCURRENCY);
+
+    let mut count = 0usize;
+    while let Some((key, res)) = results.next().await {
+        match res {
+            Ok(lines) => {
+                for l in &lines {
+                    println!("{}", l);
+                }
+                count += lines.len();
+            }
+            Err(e) => eprintln!("grep failed: s3://{}/{}: {}", bucket, key, e),
+        }
+    }
+    Ok(count)
+}
+
+/// Input format for `s3 select`.
+#[derive(Clone, Copy, PartialEq)]
+pub enum SelectFormat {
+    Csv,
+    Tsv,
+    Json,
+    Parquet,
+}
+
+impl std::str::FromStr for SelectFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "csv" => Ok(SelectFormat::Csv),
+            "tsv" => Ok(SelectFormat::Tsv),
+            "json" | "jsonl" | "ndjson" => Ok(SelectFormat::Json),
+            "parquet" => Ok(SelectFormat::Parquet),
+            _ => Err(format!("unknown format '{}' (use csv, tsv, json or parquet)", s)),
+        }
+    }
+}
+
+impl SelectFormat {
+    /// Guess the format from the key's extension (ignoring a trailing `.gz`); JSON otherwise.
+    pub fn from_key(key: &str) -> Self {
+        let base = key.strip_suffix(".gz").unwrap_or(key);
+        base.rsplit_once('.').and_then(|(_, ext)| ext.parse().ok()).unwrap_or(SelectFormat::Json)
+    }
+}
+
+/// Run the query through SelectObjectContent, printing the JSON records as they arrive.
+/// On failure the flag tells whether any event had been received, i.e. whether
+/// records may already have been printed.
+async fn select_remote(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    sql: &str,
+    format: SelectFormat,
+) -> Result<(), (aws_sdk_s3::Error, bool)> {
+    use aws_sdk_s3::types::{
+        CompressionType, CsvInput, ExpressionType, FileHeaderInfo, InputSerialization, JsonInput, JsonOutput,
+        JsonType, OutputSerialization, ParquetInput, SelectObjectContentEventStream,
+    };
+
+    let mut input = InputSerialization::builder();
+    input = match format {
+        SelectFormat::Csv | SelectFormat::Tsv => {
+            let delim = if format == SelectFormat::Tsv { "\t" } else { "," };
+            input.csv(CsvInput::builder().file_header_info(FileHeaderInfo::Use).field_delimiter(delim).build())
+        }
+        SelectFormat::Json => {
+            let json_type = if key.strip_suffix(".gz").unwrap_or(key).ends_with(".json") {
+                JsonType::Document
+            } else {
+                JsonType::Lines
+            };
+            input.json(JsonInput::builder().r#type(json_type).build())
+        }
+        SelectFormat::Parquet => input.parquet(ParquetInput::builder().build()),
+    };
+    if format != SelectFormat::Parquet && key.ends_with(".gz") {
+        input = input.compression_type(CompressionType::Gzip);
+    }
+
+    let mut resp = client
+        .select_object_content()
+        .bucket(bucket)
+        .key(key)
+        .expression(sql)
+        .expression_type(ExpressionType::Sql)
+        .input_serialization(input.build())
+        .output_serialization(OutputSerialization::builder().json(JsonOutput::builder().record_delimiter("\n").build()).build())
+        .send()
+        .await
+        .map_err(|e| (e.into(), false))?;
+
+    let mut out = std::io::stdout().lock();
+    let mut received = false;
+    while let Some(event) = resp.payload.recv().await.map_err(|e| (e.into(), received))? {
+        received = true;
+        if let SelectObjectContentEventStream::Records(records) = event
+            && let Some(blob) = records.payload()
+        {
+            let _ = out.write_all(blob.as_ref());
+        }
+    }
+    let _ = out.flush();
+    Ok(())
+}
+
+/// Download the object and evaluate the query with the local SQL engine.
+async fn select_local(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    sql: &str,
+    format: SelectFormat,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let query = crate::sql::parse(sql).map_err(|e| format!("SQL error: {}", e))?;
+
+    let resp = client.get_object().bucket(bucket).key(key).send().await.map_err(aws_sdk_s3::Error::from)?;
+    let data = resp.body.collect().await?.into_bytes();
+    if format == SelectFormat::Parquet {
+        for rec in query.execute(crate::sql::parquet_rows(data)?).map_err(|e| format!("SQL error: {}", e))? {
+            println!("{}", rec);
+        }
+        return Ok(());
+    }
+    let text = if data.starts_with(&[0x1f, 0x8b]) {
+        let mut s = String::new();
+        std::io::Read::read_to_string(&mut flate2::read::MultiGzDecoder::new(&data[..]), &mut s)?;
+        s
+    } else {
+        String::from_utf8_lossy(&data).into_owned()
+    };
+
+    let rows = match format {
+        SelectFormat::Csv => crate::sql::delimited_rows(&text, ','),
+        SelectFormat::Tsv => crate::sql::delimited_rows(&text, '\t'),
+        _ => crate::sql::json_rows(&text)?,
+    };
+    for rec in query.execute(rows).map_err(|e| format!("SQL error: {}", e))? {
+        println!("{}", rec);
+    }
+    Ok(())
+}
+
+/// `s3 select`: run `sql` against a CSV/TSV, JSON or Parquet object, printing
+/// one JSON record per line. Uses S3 Select unless `local` is set; if the
+/// endpoint rejects the request for any reason other than a missing object or
+/// access, the object is downloaded and the query evaluated locally instead.
+/// Errors after S3 Select has started streaming are returned as they are, since
+/// a local run would print the records already written a second time.
+pub async fn select_object(
+    client: &S3Client,
+    bucket: &str,
+    key: &str,
+    sql: &str,
+    format: SelectFormat,
+    local: bool,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if !local {
+        match select_remote(client, bucket, key, sql, format).await {
+            Ok(()) => return Ok(()),
+            Err((e, true)) => return Err(e.into()),
+            Err((e, _)) if matches!(e.code(), Some("NoSuchKey" | "NoSuchBucket" | "AccessDenied")) => return Err(e.into()),
+            Err((e, false)) => eprintln!("S3 Select unavailable ({}); evaluating locally", DisplayErrorContext(&e)),
+        }
+    }
+    select_local(client, bucket, key, sql, format).await
+}
+
+/// Split `bucket/prefix` (optionally `s3://bucket/prefix`) into its parts.
+pub fn parse_s3_path(path: &str) -> (String, String) {
+    let path = path.strip_prefix("s3://").unwrap_or(path);
+    match path.split_once('/') {
+        Some((bucket, prefix)) => (bucket.to_string(), prefix.to_string()),
+        None => (path.to_string(), String::new()),
+    }
+}
+
+/// Counts reported by `s3 diff`.
+#[derive(Default)]
+pub struct DiffSummary {
+    pub only_a: usize,
+    pub only_b: usize,
+    pub changed: usize,
+    pub same: usize,
+}
+
+/// Why two objects with the same relative key differ, if they do.
+fn object_difference(a: &Object, b: &Object, size_only: bool) -> Option<String> {
+    if a.size() != b.size() {
+        return Some(format!("size {} -> {}", a.size().unwrap_or(0), b.size().unwrap_or(0)));
+    }
+    if size_only || a.e_tag() == b.e_tag() {
+        return None;
+    }
+    let (ea, eb) = (a.e_tag().unwrap_or_default(), b.e_tag().unwrap_or_default());
+    // Multipart ETags depend on the part size, not only the content.
+    let note = if ea.contains('-') || eb.contains('-') { "; multipart, content may match" } else { "" };
+    Some(format!("etag {} -> {}{}", ea, eb, note))
+}
+
+/// Streams the objects under a prefix one at a time, fetching a page of
+/// ListObjectsV2 results whenever the buffer runs dry.
+struct ListingCursor<'a> {
+    client: &'a S3Client,
+    bucket: &'a str,
+    prefix: &'a str,
+    buf: std::collections::VecDeque<Object>,
+    token: Option<String>,
+    done: bool,
+}
+
+impl<'a> ListingCursor<'a> {
+    fn new(client: &'a S3Client, bucket: &'a str, prefix: &'a str) -> Self {
+        ListingCursor { client, bucket, prefix, buf: Default::default(), token: None, done: false }
+    }
+
+    async fn next(&mut self) -> Result<Option<Object>, aws_sdk_s3::Error> {
+        while self.buf.is_empty() && !self.done {
+            let page = self
+                .client
+                .list_objects_v2()
+                .bucket(self.bucket)
+                .prefix(self.prefix)
+                .set_continuation_token(self.token.take())
+                .send()
+                .await?;
+            self.buf.extend(page.contents().iter().cloned());
+            self.token = page.next_continuation_token().map(|t| t.to_string());
+            self.done = self.token.is_none();
+        }
+        Ok(self.buf.pop_front())
+    }
+}
+
+/// `s3 diff`: walk both listings in key order at the same time (ListObjectsV2
+/// returns keys sorted) and compare keys relative to each prefix. Prints
+/// `< key` (only in A), `> key` (only in B) and `M key (reason)`; with
+/// `keys_only`, prints just the relative keys missing from or different in B,
+/// which `s3 cp --recursive --keys-from` accepts.
+#[allow(clippy::too_many_arguments)]
+pub async fn diff_prefixes(
+    client: &S3Client,
+    a_bucket: &str,
+    a_prefix: &str,
+    b_bucket: &str,
+    b_prefix: &str,
+    size_only: bool,
+    keys_only: bool,
+) -> Result<DiffSummary, aws_sdk_s3::Error> {
+    let mut a = ListingCursor::new(client, a_bucket, a_prefix);
+    let mut b = ListingCursor::new(client, b_bucket, b_prefix);
+    let rel = |o: &Object, prefix: &str| o.key().unwrap_or_default()[prefix.len()..].to_string();
+
+    let mut summary = DiffSummary::default();
+    let mut next_a = a.next().await?;
+    let mut next_b = b.next().await?;
+    loop {
+        let (ka, kb) = (next_a.as_ref().map(|o| rel(o, a_prefix)), next_b.as_ref().map(|o| rel(o, b_prefix)));
+        let order = match (&ka, &kb) {
+            (None, None) => break,
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (Some(x), Some(y)) => x.cmp(y),
+        };
+        match order {
+            std::cmp::Ordering::Less => {
+                let key = ka.unwrap_or_default();
+                if keys_only {
+                    println!("{}", key);
+                } else {
+                    println!("< {}", key);
+                }
+                summary.only_a += 1;
+                next_a = a.next().await?;
+            }
+            std::cmp::Ordering::Greater => {
+                if !keys_only {
+                    println!("> {}", kb.unwrap_or_default());
+                }
+                summary.only_b += 1;
+                next_b = b.next().await?;
+            }
+            std::cmp::Ordering::Equal => {
+                let key = ka.unwrap_or_default();
+                let (oa, ob) = (next_a.as_ref().expect("key present"), next_b.as_ref().expect("key present"));
+                match object_difference(oa, ob, size_only) {
+                    Some(_) if keys_only => {
+                        println!("{}", key);
+                        summary.changed += 1;
+                    }
+                    Some(reason) => {
+                        println!("M {} ({})", key, reason);
+                        summary.changed += 1;
+                    }
+                    None => summary.same += 1,
+                }
+                next_a = a.next().await?;
+                next_b = b.next().await?;
+            }
+        }
+    }
+    Ok(summary)
+}
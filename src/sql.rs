@@ -0,0 +1,1010 @@
+// Local evaluator for the subset of S3 Select SQL we use, for endpoints that
+// don't implement SelectObjectContent:
+//
+//   SELECT * | expr [AS name], ... | COUNT(*), SUM(expr), MIN/MAX/AVG(expr)
+//   FROM S3Object[*] [[AS] alias]
+//   [WHERE expr] [LIMIT n]
+//
+// Expressions support paths (`s.a.b`, `s."Name"`, `s.list[0]`), string /
+// number / boolean / NULL literals, arithmetic, comparisons, AND/OR/NOT,
+// [NOT] LIKE, [NOT] IN (...), [NOT] BETWEEN, IS [NOT] NULL, and the functions
+// LOWER, UPPER, CHAR_LENGTH and CAST(x AS INT|FLOAT|STRING|BOOL).
+// Rows are JSON values; CSV rows become objects keyed by header and `_1`, `_2`, ...
+// Comparisons and AND/OR/NOT treat NULL (missing) values as unknown, as SQL does;
+// WHERE keeps only rows where the condition is true.
+
+use serde_json::{Map, Value};
+use std::cmp::Ordering;
+
+pub struct Query {
+    /// Empty means `SELECT *`.
+    items: Vec<SelectItem>,
+    alias: Option<String>,
+    filter: Option<Expr>,
+    limit: Option<usize>,
+}
+
+enum SelectItem {
+    Expr(Expr, Option<String>),
+    /// Aggregate function; `None` argument means `COUNT(*)`.
+    Agg(AggFn, Option<Expr>, Option<String>),
+}
+
+#[derive(Clone, Copy)]
+enum AggFn {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+enum PathSeg {
+    Key { name: String, quoted: bool },
+    Index(usize),
+}
+
+enum Expr {
+    Lit(Value),
+    Path(Vec<PathSeg>),
+    Neg(Box<Expr>),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, String, Box<Expr>),
+    IsNull(Box<Expr>, bool),
+    /// The pattern's regex is compiled once at parse time when it is a literal.
+    Like(Box<Expr>, Box<Expr>, Option<regex::Regex>, bool),
+    In(Box<Expr>, Vec<Expr>, bool),
+    Between(Box<Expr>, Box<Expr>, Box<Expr>, bool),
+    Func(String, Vec<Expr>),
+}
+
+// ---- tokenizer ----
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Ident(String),
+    Quoted(String),
+    Str(String),
+    Num(String),
+    Sym(String),
+}
+
+fn tokenize(sql: &str) -> Result<Vec<Tok>, String> {
+    let chars: Vec<char> = sql.chars().collect();
+    let mut toks = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c == '\'' || c == '"' {
+            // '' / "" inside a literal is an escaped quote
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err("unterminated quoted string".to_string()),
+                    Some(&q) if q == c && chars.get(i + 1) == Some(&c) => {
+                        s.push(c);
+                        i += 2;
+                    }
+                    Some(&q) if q == c => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&other) => {
+                        s.push(other);
+                        i += 1;
+                    }
+                }
+            }
+            toks.push(if c == '\'' { Tok::Str(s) } else { Tok::Quoted(s) });
+        } else if c.is_ascii_digit() {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            toks.push(Tok::Num(chars[start..i].iter().collect()));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            toks.push(Tok::Ident(chars[start..i].iter().collect()));
+        } else {
+            let two: String = chars[i..(i + 2).min(chars.len())].iter().collect();
+            if ["!=", "<>", "<=", ">="].contains(&two.as_str()) {
+                toks.push(Tok::Sym(two));
+                i += 2;
+            } else if "(),.*[]=<>+-/%".contains(c) {
+                toks.push(Tok::Sym(c.to_string()));
+                i += 1;
+            } else {
+                return Err(format!("unexpected character '{}'", c));
+            }
+        }
+    }
+    Ok(toks)
+}
+
+// ---- parser ----
+
+struct Parser {
+    toks: Vec<Tok>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Tok> {
+        self.toks.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Tok> {
+        let t = self.toks.get(self.pos).cloned();
+        self.pos += 1;
+        t
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Ident(s)) if s.eq_ignore_ascii_case(kw))
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        if self.is_keyword(kw) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, kw: &str) -> Result<(), String> {
+        if self.eat_keyword(kw) { Ok(()) } else { Err(format!("expected {}", kw)) }
+    }
+
+    fn is_sym(&self, sym: &str) -> bool {
+        matches!(self.peek(), Some(Tok::Sym(s)) if s == sym)
+    }
+
+    fn eat_sym(&mut self, sym: &str) -> bool {
+        if self.is_sym(sym) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_sym(&mut self, sym: &str) -> Result<(), String> {
+        if self.eat_sym(sym) { Ok(()) } else { Err(format!("expected '{}'", sym)) }
+    }
+
+    fn query(&mut self) -> Result<Query, String> {
+        self.expect_keyword("SELECT")?;
+        let mut items = Vec::new();
+        if !self.eat_sym("*") {
+            loop {
+                items.push(self.select_item()?);
+                if !self.eat_sym(",") {
+                    break;
+                }
+            }
+        }
+        self.expect_keyword("FROM")?;
+        match self.next() {
+            Some(Tok::Ident(s)) if s.eq_ignore_ascii_case("S3Object") => {}
+            _ => return Err("expected FROM S3Object".to_string()),
+        }
+        if self.eat_sym("[") {
+            self.expect_sym("*")?;
+            self.expect_sym("]")?;
+        }
+        if self.is_sym(".") {
+            return Err("paths after S3Object[*] are not supported locally".to_string());
+        }
+        self.eat_keyword("AS");
+        let alias = match self.peek() {
+            Some(Tok::Ident(s)) if !["WHERE", "LIMIT"].iter().any(|k| s.eq_ignore_ascii_case(k)) => {
+                let s = s.clone();
+                self.pos += 1;
+                Some(s)
+            }
+            _ => None,
+        };
+        let filter = if self.eat_keyword("WHERE") { Some(self.expr()?) } else { None };
+        let limit = if self.eat_keyword("LIMIT") {
+            match self.next() {
+                Some(Tok::Num(n)) => Some(n.parse().map_err(|_| "invalid LIMIT".to_string())?),
+                _ => return Err("expected number after LIMIT".to_string()),
+            }
+        } else {
+            None
+        };
+        if let Some(t) = self.peek() {
+            return Err(format!("unexpected {:?}", t));
+        }
+        Ok(Query { items, alias, filter, limit })
+    }
+
+    fn select_item(&mut self) -> Result<SelectItem, String> {
+        let agg = match self.peek() {
+            Some(Tok::Ident(s)) if self.toks.get(self.pos + 1) == Some(&Tok::Sym("(".into())) => {
+                match s.to_ascii_uppercase().as_str() {
+                    "COUNT" => Some(AggFn::Count),
+                    "SUM" => Some(AggFn::Sum),
+                    "MIN" => Some(AggFn::Min),
+                    "MAX" => Some(AggFn::Max),
+                    "AVG" => Some(AggFn::Avg),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        let item = match agg {
+            Some(f) => {
+                self.pos += 2;
+                let arg = if self.eat_sym("*") { None } else { Some(self.expr()?) };
+                self.expect_sym(")")?;
+                SelectItem::Agg(f, arg, None)
+            }
+            None => SelectItem::Expr(self.expr()?, None),
+        };
+        let name = if self.eat_keyword("AS") {
+            match self.next() {
+                Some(Tok::Ident(s)) | Some(Tok::Quoted(s)) => Some(s),
+                _ => return Err("expected name after AS".to_string()),
+            }
+        } else {
+            None
+        };
+        Ok(match item {
+            SelectItem::Expr(e, _) => SelectItem::Expr(e, name),
+            SelectItem::Agg(f, a, _) => SelectItem::Agg(f, a, name),
+        })
+    }
+
+    fn expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.and_expr()?;
+        while self.eat_keyword("OR") {
+            left = Expr::Binary(Box::new(left), "OR".into(), Box::new(self.and_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, String> {
+        let mut left = self.not_expr()?;
+        while self.eat_keyword("AND") {
+            left = Expr::Binary(Box::new(left), "AND".into(), Box::new(self.not_expr()?));
+        }
+        Ok(left)
+    }
+
+    fn not_expr(&mut self) -> Result<Expr, String> {
+        if self.eat_keyword("NOT") {
+            return Ok(Expr::Not(Box::new(self.not_expr()?)));
+        }
+        self.comparison()
+    }
+
+    fn comparison(&mut self) -> Result<Expr, String> {
+        let left = self.additive()?;
+        if let Some(Tok::Sym(op)) = self.peek()
+            && ["=", "!=", "<>", "<", "<=", ">", ">="].contains(&op.as_str())
+        {
+            let op = if op == "<>" { "!=".to_string() } else { op.clone() };
+            self.pos += 1;
+            return Ok(Expr::Binary(Box::new(left), op, Box::new(self.additive()?)));
+        }
+        if self.eat_keyword("IS") {
+            let negated = self.eat_keyword("NOT");
+            self.expect_keyword("NULL")?;
+            return Ok(Expr::IsNull(Box::new(left), negated));
+        }
+        let negated = self.eat_keyword("NOT");
+        if self.eat_keyword("LIKE") {
+            let pattern = self.additive()?;
+            let compiled = match &pattern {
+                Expr::Lit(v) => as_text(v).map(|p| like_to_regex(&p)).transpose()?,
+                _ => None,
+            };
+            return Ok(Expr::Like(Box::new(left), Box::new(pattern), compiled, negated));
+        }
+        if self.eat_keyword("IN") {
+            self.expect_sym("(")?;
+            let mut list = vec![self.expr()?];
+            while self.eat_sym(",") {
+                list.push(self.expr()?);
+            }
+            self.expect_sym(")")?;
+            return Ok(Expr::In(Box::new(left), list, negated));
+        }
+        if self.eat_keyword("BETWEEN") {
+            let low = self.additive()?;
+            self.expect_keyword("AND")?;
+            let high = self.additive()?;
+            return Ok(Expr::Between(Box::new(left), Box::new(low), Box::new(high), negated));
+        }
+        if negated {
+            return Err("expected LIKE, IN or BETWEEN after NOT".to_string());
+        }
+        Ok(left)
+    }
+
+    fn additive(&mut self) -> Result<Expr, String> {
+        let mut left = self.multiplicative()?;
+        while let Some(op) = ["+", "-"].into_iter().find(|s| self.is_sym(s)) {
+            self.pos += 1;
+            left = Expr::Binary(Box::new(left), op.into(), Box::new(self.multiplicative()?));
+        }
+        Ok(left)
+    }
+
+    fn multiplicative(&mut self) -> Result<Expr, String> {
+        let mut left = self.unary()?;
+        while let Some(op) = ["*", "/", "%"].into_iter().find(|s| self.is_sym(s)) {
+            self.pos += 1;
+            left = Expr::Binary(Box::new(left), op.into(), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Expr, String> {
+        if self.eat_sym("-") {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Expr, String> {
+        match self.next() {
+            Some(Tok::Num(n)) => {
+                let v: f64 = n.parse().map_err(|_| format!("invalid number {}", n))?;
+                Ok(Expr::Lit(number(v)))
+            }
+            Some(Tok::Str(s)) => Ok(Expr::Lit(Value::String(s))),
+            Some(Tok::Sym(s)) if s == "(" => {
+                let e = self.expr()?;
+                self.expect_sym(")")?;
+                Ok(e)
+            }
+            Some(Tok::Ident(s)) if s.eq_ignore_ascii_case("NULL") => Ok(Expr::Lit(Value::Null)),
+            Some(Tok::Ident(s)) if s.eq_ignore_ascii_case("TRUE") => Ok(Expr::Lit(Value::Bool(true))),
+            Some(Tok::Ident(s)) if s.eq_ignore_ascii_case("FALSE") => Ok(Expr::Lit(Value::Bool(false))),
+            Some(Tok::Ident(s)) if self.is_sym("(") => {
+                self.pos += 1;
+                let name = s.to_ascii_uppercase();
+                let mut args = vec![self.expr()?];
+                if name == "CAST" {
+                    self.expect_keyword("AS")?;
+                    match self.next() {
+                        Some(Tok::Ident(t)) => args.push(Expr::Lit(Value::String(t.to_ascii_uppercase()))),
+                        _ => return Err("expected type in CAST".to_string()),
+                    }
+                } else {
+                    while self.eat_sym(",") {
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect_sym(")")?;
+                Ok(Expr::Func(name, args))
+            }
+            Some(Tok::Ident(s)) => self.path(s, false),
+            Some(Tok::Quoted(s)) => self.path(s, true),
+            other => Err(format!("unexpected {:?}", other)),
+        }
+    }
+
+    fn path(&mut self, first: String, quoted: bool) -> Result<Expr, String> {
+        let mut segs = vec![PathSeg::Key { name: first, quoted }];
+        loop {
+            if self.eat_sym(".") {
+                match self.next() {
+                    Some(Tok::Ident(s)) => segs.push(PathSeg::Key { name: s, quoted: false }),
+                    Some(Tok::Quoted(s)) => segs.push(PathSeg::Key { name: s, quoted: true }),
+                    _ => return Err("expected name after '.'".to_string()),
+                }
+            } else if self.eat_sym("[") {
+                match self.next() {
+                    Some(Tok::Num(n)) => segs.push(PathSeg::Index(n.parse().map_err(|_| "invalid index".to_string())?)),
+                    _ => return Err("expected index".to_string()),
+                }
+                self.expect_sym("]")?;
+            } else {
+                break;
+            }
+        }
+        Ok(Expr::Path(segs))
+    }
+}
+
+pub fn parse(sql: &str) -> Result<Query, String> {
+    Parser { toks: tokenize(sql)?, pos: 0 }.query()
+}
+
+// ---- evaluation ----
+
+fn number(v: f64) -> Value {
+    if v.fract() == 0.0 && v.abs() < 9e15 {
+        Value::from(v as i64)
+    } else {
+        serde_json::Number::from_f64(v).map(Value::Number).unwrap_or(Value::Null)
+    }
+}
+
+/// Numbers, and strings that look like numbers (CSV fields are all strings).
+fn as_f64(v: &Value) -> Option<f64> {
+    match v {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn as_text(v: &Value) -> Option<String> {
+    match v {
+        Value::Null => None,
+        Value::String(s) => Some(s.clone()),
+        other => Some(other.to_string()),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::Null, _) | (_, Value::Null) => None,
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::Bool(x), Value::Bool(y)) => Some(x.cmp(y)),
+        (Value::Number(_), _) | (_, Value::Number(_)) => as_f64(a)?.partial_cmp(&as_f64(b)?),
+        _ => (a == b).then_some(Ordering::Equal),
+    }
+}
+
+fn truthy(v: &Value) -> bool {
+    matches!(v, Value::Bool(true))
+}
+
+/// SQL three-valued AND/OR: a NULL (or non-boolean) operand is unknown, and
+/// the result is unknown unless the other operand decides it.
+fn logic(op: &str, a: &Value, b: &Value) -> Value {
+    let (a, b) = (a.as_bool(), b.as_bool());
+    let decisive = op == "OR";
+    match (a, b) {
+        (Some(x), _) | (_, Some(x)) if x == decisive => Value::Bool(decisive),
+        (Some(_), Some(_)) => Value::Bool(!decisive),
+        _ => Value::Null,
+    }
+}
+
+fn like_to_regex(pattern: &str) -> Result<regex::Regex, String> {
+    let mut re = String::from("(?s)^");
+    for c in pattern.chars() {
+        match c {
+            '%' => re.push_str(".*"),
+            '_' => re.push('.'),
+            other => re.push_str(&regex::escape(&other.to_string())),
+        }
+    }
+    re.push('$');
+    regex::Regex::new(&re).map_err(|e| e.to_string())
+}
+
+fn lookup<'a>(v: &'a Value, seg: &PathSeg) -> Option<&'a Value> {
+    match (seg, v) {
+        (PathSeg::Index(i), Value::Array(a)) => a.get(*i),
+        (PathSeg::Key { name, quoted }, Value::Object(m)) => m.get(name).or_else(|| {
+            // Unquoted identifiers are case-insensitive in S3 Select.
+            if *quoted { None } else { m.iter().find(|(k, _)| k.eq_ignore_ascii_case(name)).map(|(_, v)| v) }
+        }),
+        _ => None,
+    }
+}
+
+impl Query {
+    fn eval(&self, e: &Expr, row: &Value) -> Result<Value, String> {
+        Ok(match e {
+            Expr::Lit(v) => v.clone(),
+            Expr::Path(segs) => {
+                // A leading alias (or S3Object itself) refers to the row.
+                let skip = match &segs[0] {
+                    PathSeg::Key { name, .. }
+                        if segs.len() > 1
+                            && (self.alias.as_deref().is_some_and(|a| a.eq_ignore_ascii_case(name))
+                                || name.eq_ignore_ascii_case("S3Object")) =>
+                    {
+                        1
+                    }
+                    _ => 0,
+                };
+                let mut cur = Some(row);
+                for seg in &segs[skip..] {
+                    cur = cur.and_then(|v| lookup(v, seg));
+                }
+                cur.cloned().unwrap_or(Value::Null)
+            }
+            Expr::Neg(x) => as_f64(&self.eval(x, row)?).map(|n| number(-n)).unwrap_or(Value::Null),
+            Expr::Not(x) => match self.eval(x, row)? {
+                Value::Bool(b) => Value::Bool(!b),
+                _ => Value::Null,
+            },
+            Expr::Binary(l, op, r) if op == "AND" || op == "OR" => logic(op, &self.eval(l, row)?, &self.eval(r, row)?),
+            Expr::Binary(l, op, r) => {
+                let (a, b) = (self.eval(l, row)?, self.eval(r, row)?);
+                match op.as_str() {
+                    "+" | "-" | "*" | "/" | "%" => match (as_f64(&a), as_f64(&b)) {
+                        (Some(x), Some(y)) => match op.as_str() {
+                            "+" => number(x + y),
+                            "-" => number(x - y),
+                            "*" => number(x * y),
+                            "/" if y != 0.0 => number(x / y),
+                            "%" if y != 0.0 => number(x % y),
+                            _ => Value::Null,
+                        },
+                        _ => Value::Null,
+                    },
+                    _ if a.is_null() || b.is_null() => Value::Null,
+                    _ => match compare(&a, &b) {
+                        None => Value::Bool(op == "!=" && a != b),
+                        Some(ord) => Value::Bool(match op.as_str() {
+                            "=" => ord == Ordering::Equal,
+                            "!=" => ord != Ordering::Equal,
+                            "<" => ord == Ordering::Less,
+                            "<=" => ord != Ordering::Greater,
+                            ">" => ord == Ordering::Greater,
+                            ">=" => ord != Ordering::Less,
+                            _ => return Err(format!("unknown operator {}", op)),
+                        }),
+                    },
+                }
+            }
+            Expr::IsNull(x, negated) => Value::Bool(self.eval(x, row)?.is_null() != *negated),
+            Expr::Like(x, p, compiled, negated) => match (as_text(&self.eval(x, row)?), as_text(&self.eval(p, row)?)) {
+                (Some(s), Some(_)) if let Some(re) = compiled => Value::Bool(re.is_match(&s) != *negated),
+                (Some(s), Some(p)) => Value::Bool(like_to_regex(&p)?.is_match(&s) != *negated),
+                _ => Value::Null,
+            },
+            Expr::In(x, list, negated) => {
+                let v = self.eval(x, row)?;
+                if v.is_null() {
+                    return Ok(Value::Null);
+                }
+                let mut found = false;
+                for item in list {
+                    if compare(&v, &self.eval(item, row)?) == Some(Ordering::Equal) {
+                        found = true;
+                        break;
+                    }
+                }
+                Value::Bool(found != *negated)
+            }
+            Expr::Between(x, lo, hi, negated) => {
+                let v = self.eval(x, row)?;
+                if v.is_null() {
+                    return Ok(Value::Null);
+                }
+                let above = compare(&v, &self.eval(lo, row)?).is_some_and(|o| o != Ordering::Less);
+                let below = compare(&v, &self.eval(hi, row)?).is_some_and(|o| o != Ordering::Greater);
+                Value::Bool((above && below) != *negated)
+            }
+            Expr::Func(name, args) => {
+                let v = self.eval(&args[0], row)?;
+                match name.as_str() {
+                    "LOWER" => as_text(&v).map(|s| Value::String(s.to_lowercase())).unwrap_or(Value::Null),
+                    "UPPER" => as_text(&v).map(|s| Value::String(s.to_uppercase())).unwrap_or(Value::Null),
+                    "CHAR_LENGTH" | "CHARACTER_LENGTH" => {
+                        as_text(&v).map(|s| Value::from(s.chars().count())).unwrap_or(Value::Null)
+                    }
+                    "CAST" => {
+                        let ty = self.eval(&args[1], row)?;
+                        match ty.as_str().unwrap_or_default() {
+                            "INT" | "INTEGER" => as_f64(&v).map(|n| Value::from(n.trunc() as i64)).unwrap_or(Value::Null),
+                            "FLOAT" | "DECIMAL" => as_f64(&v).map(number).unwrap_or(Value::Null),
+                            "STRING" => as_text(&v).map(Value::String).unwrap_or(Value::Null),
+                            "BOOL" => match &v {
+                                Value::Bool(_) => v.clone(),
+                                other => match as_text(other).map(|s| s.to_ascii_lowercase()).as_deref() {
+                                    Some("true") => Value::Bool(true),
+                                    Some("false") => Value::Bool(false),
+                                    _ => Value::Null,
+                                },
+                            },
+                            other => return Err(format!("unsupported CAST type {}", other)),
+                        }
+                    }
+                    other => return Err(format!("unsupported function {}", other)),
+                }
+            }
+        })
+    }
+
+    fn column_name(&self, i: usize) -> String {
+        let (expr, alias) = match &self.items[i] {
+            SelectItem::Expr(e, a) => (Some(e), a),
+            SelectItem::Agg(_, _, a) => (None, a),
+        };
+        if let Some(a) = alias {
+            return a.clone();
+        }
+        match expr {
+            Some(Expr::Path(segs)) => match segs.last() {
+                Some(PathSeg::Key { name, .. }) => name.clone(),
+                _ => format!("_{}", i + 1),
+            },
+            _ => format!("_{}", i + 1),
+        }
+    }
+
+    /// Run the query over `rows`, returning the output records.
+    pub fn execute(&self, rows: impl IntoIterator<Item = Value>) -> Result<Vec<Value>, String> {
+        let aggregate = self.items.iter().any(|i| matches!(i, SelectItem::Agg(..)));
+        if aggregate && self.items.iter().any(|i| matches!(i, SelectItem::Expr(..))) {
+            return Err("cannot mix aggregate and non-aggregate columns".to_string());
+        }
+
+        let mut out = Vec::new();
+        // (count, sum, min, max) per aggregate column
+        let mut acc: Vec<(u64, f64, Option<Value>, Option<Value>)> = vec![(0, 0.0, None, None); self.items.len()];
+        for row in rows {
+            if let Some(f) = &self.filter
+                && !truthy(&self.eval(f, &row)?)
+            {
+                continue;
+            }
+            if aggregate {
+                for (i, item) in self.items.iter().enumerate() {
+                    let SelectItem::Agg(_, arg, _) = item else { continue };
+                    let v = match arg {
+                        Some(e) => self.eval(e, &row)?,
+                        None => Value::Bool(true),
+                    };
+                    if v.is_null() {
+                        continue;
+                    }
+                    let a = &mut acc[i];
+                    a.0 += 1;
+                    a.1 += as_f64(&v).unwrap_or(0.0);
+                    if a.2.as_ref().is_none_or(|m| compare(&v, m) == Some(Ordering::Less)) {
+                        a.2 = Some(v.clone());
+                    }
+                    if a.3.as_ref().is_none_or(|m| compare(&v, m) == Some(Ordering::Greater)) {
+                        a.3 = Some(v);
+                    }
+                }
+                continue;
+            }
+            if self.limit.is_some_and(|l| out.len() >= l) {
+                break;
+            }
+            if self.items.is_empty() {
+                out.push(without_positional(row));
+            } else {
+                let mut rec = Map::new();
+                for (i, item) in self.items.iter().enumerate() {
+                    if let SelectItem::Expr(e, _) = item {
+                        rec.insert(self.column_name(i), self.eval(e, &row)?);
+                    }
+                }
+                out.push(Value::Object(rec));
+            }
+        }
+
+        if aggregate {
+            let mut rec = Map::new();
+            for (i, item) in self.items.iter().enumerate() {
+                let SelectItem::Agg(f, _, _) = item else { continue };
+                let (count, sum, min, max) = acc[i].clone();
+                let v = match f {
+                    AggFn::Count => Value::from(count),
+                    AggFn::Sum => number(sum),
+                    AggFn::Avg if count > 0 => number(sum / count as f64),
+                    AggFn::Avg => Value::Null,
+                    AggFn::Min => min.unwrap_or(Value::Null),
+                    AggFn::Max => max.unwrap_or(Value::Null),
+                };
+                rec.insert(self.column_name(i), v);
+            }
+            out.push(Value::Object(rec));
+        }
+        Ok(out)
+    }
+}
+
+// ---- input formats ----
+
+/// Split delimited text into records, honouring double-quoted fields (which
+/// may contain the delimiter, newlines and `""` escapes).
+fn parse_delimited(text: &str, delim: char) -> Vec<Vec<String>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                other => field.push(other),
+            }
+        } else if c == '"' {
+            in_quotes = true;
+        } else if c == delim {
+            record.push(std::mem::take(&mut field));
+        } else if c == '\n' || c == '\r' {
+            if c == '\r' && chars.peek() == Some(&'\n') {
+                chars.next();
+            }
+            record.push(std::mem::take(&mut field));
+            records.push(std::mem::take(&mut record));
+        } else {
+            field.push(c);
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records
+}
+
+/// CSV/TSV rows with a header line, as objects keyed by header name and by
+/// position (`_1`, `_2`, ...), like S3 Select with `FileHeaderInfo=USE`.
+pub fn delimited_rows(text: &str, delim: char) -> Vec<Value> {
+    let mut records = parse_delimited(text, delim).into_iter();
+    let header = records.next().unwrap_or_default();
+    records
+        .map(|fields| {
+            let mut row = Map::new();
+            for (i, f) in fields.into_iter().enumerate() {
+                if let Some(h) = header.get(i) {
+                    row.insert(h.clone(), Value::String(f.clone()));
+                }
+                row.insert(format!("_{}", i + 1), Value::String(f));
+            }
+            Value::Object(row)
+        })
+        .collect()
+}
+
+/// `SELECT *` on a CSV row with a header returns only the named columns; the
+/// `_1`, `_2`, ... keys exist just so queries can refer to columns by position.
+fn without_positional(row: Value) -> Value {
+    let is_positional = |k: &str| k.strip_prefix('_').is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+    match row {
+        Value::Object(m) if m.keys().any(|k| !is_positional(k)) => {
+            Value::Object(m.into_iter().filter(|(k, _)| !is_positional(k)).collect())
+        }
+        other => other,
+    }
+}
+
+/// JSON rows: a single document (an array yields one row per element), or
+/// JSON Lines when the text is not one document.
+pub fn json_rows(text: &str) -> Result<Vec<Value>, String> {
+    if let Ok(doc) = serde_json::from_str::<Value>(text) {
+        return Ok(match doc {
+            Value::Array(items) => items,
+            other => vec![other],
+        });
+    }
+    text.lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| serde_json::from_str(l).map_err(|e| format!("invalid JSON line: {}", e)))
+        .collect()
+}
+
+/// Rows of a Parquet file, one JSON object per row (nested groups become
+/// objects, lists become arrays).
+pub fn parquet_rows(data: bytes::Bytes) -> Result<Vec<Value>, String> {
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+
+    let reader = SerializedFileReader::new(data).map_err(|e| format!("invalid Parquet file: {}", e))?;
+    let rows = reader.get_row_iter(None).map_err(|e| format!("invalid Parquet file: {}", e))?;
+    rows.map(|r| r.map(|row| row.to_json_value()).map_err(|e| format!("invalid Parquet row: {}", e))).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn run(sql: &str, rows: Vec<Value>) -> Vec<Value> {
+        parse(sql).unwrap().execute(rows).unwrap()
+    }
+
+    /// Evaluate a single expression against `row`.
+    fn eval(expr: &str, row: Value) -> Value {
+        let out = run(&format!("SELECT {} AS v FROM S3Object s", expr), vec![row]);
+        out[0]["v"].clone()
+    }
+
+    #[test]
+    fn tokenize_literals_and_symbols() {
+        let toks = tokenize("SELECT s.\"Na me\", 'it''s' FROM x WHERE a<>1.5 AND b>=2").unwrap();
+        assert_eq!(
+            toks,
+            vec![
+                Tok::Ident("SELECT".into()),
+                Tok::Ident("s".into()),
+                Tok::Sym(".".into()),
+                Tok::Quoted("Na me".into()),
+                Tok::Sym(",".into()),
+                Tok::Str("it's".into()),
+                Tok::Ident("FROM".into()),
+                Tok::Ident("x".into()),
+                Tok::Ident("WHERE".into()),
+                Tok::Ident("a".into()),
+                Tok::Sym("<>".into()),
+                Tok::Num("1.5".into()),
+                Tok::Ident("AND".into()),
+                Tok::Ident("b".into()),
+                Tok::Sym(">=".into()),
+                Tok::Num("2".into()),
+            ]
+        );
+        assert!(tokenize("'open").is_err());
+        assert!(tokenize("a ; b").is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(parse("SELECT * FROM table").is_err());
+        assert!(parse("SELECT * FROM S3Object LIMIT x").is_err());
+        assert!(parse("SELECT * FROM S3Object s WHERE").is_err());
+        assert!(parse("SELECT * FROM S3Object s extra").is_err());
+        assert!(parse("SELECT a NOT 1 FROM S3Object").is_err());
+        assert!(parse("SELECT * FROM S3Object[*].a").is_err());
+    }
+
+    #[test]
+    fn operator_precedence() {
+        assert_eq!(eval("1 + 2 * 3", json!({})), json!(7));
+        assert_eq!(eval("(1 + 2) * 3", json!({})), json!(9));
+        assert_eq!(eval("10 - 4 - 3", json!({})), json!(3));
+        assert_eq!(eval("-2 * 3 + 7 % 4", json!({})), json!(-3));
+        assert_eq!(eval("7 / 2", json!({})), json!(3.5));
+        assert_eq!(eval("1 / 0", json!({})), Value::Null);
+        // AND binds tighter than OR; NOT tighter than AND.
+        assert_eq!(eval("TRUE OR TRUE AND FALSE", json!({})), json!(true));
+        assert_eq!(eval("NOT FALSE AND FALSE", json!({})), json!(false));
+        assert_eq!(eval("1 + 1 = 2 AND 3 > 2", json!({})), json!(true));
+    }
+
+    #[test]
+    fn paths_and_comparisons() {
+        let row = json!({"Name": "Ann", "age": "42", "tags": ["x", "y"], "addr": {"city": "Oslo"}});
+        assert_eq!(eval("s.name", row.clone()), json!("Ann"));
+        assert_eq!(eval("s.\"name\"", row.clone()), Value::Null);
+        assert_eq!(eval("s.tags[1]", row.clone()), json!("y"));
+        assert_eq!(eval("s.addr.city", row.clone()), json!("Oslo"));
+        assert_eq!(eval("s.age > 9", row.clone()), json!(true));
+        assert_eq!(eval("s.age > '9'", row.clone()), json!(false));
+        assert_eq!(eval("s.age BETWEEN 40 AND 50", row.clone()), json!(true));
+        assert_eq!(eval("s.name IN ('Bob', 'Ann')", row.clone()), json!(true));
+        assert_eq!(eval("s.name NOT IN ('Bob', 'Ann')", row), json!(false));
+    }
+
+    #[test]
+    fn like_patterns() {
+        let row = json!({"k": "logs/2024/a_b.txt"});
+        assert_eq!(eval("s.k LIKE 'logs/%'", row.clone()), json!(true));
+        assert_eq!(eval("s.k LIKE '%.TXT'", row.clone()), json!(false));
+        assert_eq!(eval("s.k LIKE 'logs/____/%'", row.clone()), json!(true));
+        assert_eq!(eval("s.k NOT LIKE '%.csv'", row.clone()), json!(true));
+        // Regex metacharacters in the pattern are literal.
+        assert_eq!(eval("'a.b' LIKE 'a.b'", json!({})), json!(true));
+        assert_eq!(eval("'axb' LIKE 'a.b'", json!({})), json!(false));
+        assert_eq!(eval("s.missing LIKE '%'", row.clone()), Value::Null);
+        // Patterns taken from the row are compiled per row.
+        assert_eq!(eval("s.k LIKE s.p", json!({"k": "abc", "p": "a%"})), json!(true));
+        assert_eq!(eval("s.k LIKE s.missing", row), Value::Null);
+    }
+
+    #[test]
+    fn cast_and_functions() {
+        assert_eq!(eval("CAST('42' AS INT)", json!({})), json!(42));
+        assert_eq!(eval("CAST('4.7' AS INTEGER)", json!({})), json!(4));
+        assert_eq!(eval("CAST('4.5' AS FLOAT)", json!({})), json!(4.5));
+        assert_eq!(eval("CAST(12 AS STRING)", json!({})), json!("12"));
+        assert_eq!(eval("CAST('TRUE' AS BOOL)", json!({})), json!(true));
+        assert_eq!(eval("CAST('abc' AS INT)", json!({})), Value::Null);
+        assert_eq!(eval("UPPER(s.x)", json!({"x": "abc"})), json!("ABC"));
+        assert_eq!(eval("LOWER('ÄB')", json!({})), json!("äb"));
+        assert_eq!(eval("CHAR_LENGTH('héllo')", json!({})), json!(5));
+        assert!(parse("SELECT CAST(1 AS DATE) FROM S3Object").unwrap().execute(vec![json!({})]).is_err());
+    }
+
+    #[test]
+    fn null_handling() {
+        let row = json!({"a": null, "t": true, "f": false});
+        assert_eq!(eval("s.a IS NULL", row.clone()), json!(true));
+        assert_eq!(eval("s.missing IS NOT NULL", row.clone()), json!(false));
+        assert_eq!(eval("s.a = 1", row.clone()), Value::Null);
+        assert_eq!(eval("s.a != 1", row.clone()), Value::Null);
+        assert_eq!(eval("NOT (s.a = 1)", row.clone()), Value::Null);
+        assert_eq!(eval("s.a + 1", row.clone()), Value::Null);
+        assert_eq!(eval("s.a IN (1, 2)", row.clone()), Value::Null);
+        assert_eq!(eval("s.a BETWEEN 1 AND 2", row.clone()), Value::Null);
+        // Three-valued AND / OR.
+        assert_eq!(eval("s.a = 1 AND s.f", row.clone()), json!(false));
+        assert_eq!(eval("s.a = 1 AND s.t", row.clone()), Value::Null);
+        assert_eq!(eval("s.a = 1 OR s.t", row.clone()), json!(true));
+        assert_eq!(eval("s.a = 1 OR s.f", row.clone()), Value::Null);
+        assert_eq!(eval("s.t AND s.a = 1", row.clone()), Value::Null);
+        assert_eq!(eval("s.f OR s.t", row), json!(true));
+
+        // NOT over an unknown comparison must not select rows with NULLs.
+        let rows = vec![json!({"n": 1}), json!({"n": null}), json!({})];
+        assert_eq!(run("SELECT * FROM S3Object s WHERE NOT (s.n = 2)", rows.clone()), vec![json!({"n": 1})]);
+        assert_eq!(run("SELECT COUNT(*), COUNT(s.n) AS c FROM S3Object s", rows), vec![json!({"_1": 3, "c": 1})]);
+    }
+
+    #[test]
+    fn where_limit_and_projection() {
+        let rows: Vec<Value> = (1..=10).map(|n| json!({"n": n, "odd": n % 2 == 1})).collect();
+        let out = run("SELECT s.n FROM S3Object s WHERE s.odd LIMIT 3", rows.clone());
+        assert_eq!(out, vec![json!({"n": 1}), json!({"n": 3}), json!({"n": 5})]);
+        assert!(run("SELECT * FROM S3Object LIMIT 0", rows.clone()).is_empty());
+        assert_eq!(run("SELECT * FROM S3Object[*] AS s WHERE s.n = 10", rows.clone()), vec![json!({"n": 10, "odd": false})]);
+        let out = run("SELECT SUM(s.n) AS total, MIN(s.n), MAX(s.n), AVG(s.n) AS avg FROM S3Object s", rows);
+        assert_eq!(out, vec![json!({"total": 55, "_2": 1, "_3": 10, "avg": 5.5})]);
+        assert!(parse("SELECT s.n, COUNT(*) FROM S3Object s").unwrap().execute(vec![]).is_err());
+    }
+
+    #[test]
+    fn csv_rows() {
+        let text = "name,note\r\nAnn,\"a, \"\"quoted\"\"\nline\"\nBob,\n";
+        let rows = delimited_rows(text, ',');
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["name"], json!("Ann"));
+        assert_eq!(rows[0]["note"], json!("a, \"quoted\"\nline"));
+        assert_eq!(rows[0]["_2"], rows[0]["note"]);
+        assert_eq!(rows[1]["note"], json!(""));
+        // Positional keys are hidden from SELECT * but usable in expressions.
+        assert_eq!(run("SELECT * FROM S3Object s WHERE s._1 = 'Bob'", rows), vec![json!({"name": "Bob", "note": ""})]);
+    }
+
+    #[test]
+    fn tsv_rows() {
+        let rows = delimited_rows("a\tb\n1\t2\t3\n4", '\t');
+        assert_eq!(rows, vec![json!({"a": "1", "b": "2", "_1": "1", "_2": "2", "_3": "3"}), json!({"a": "4", "_1": "4"})]);
+        assert!(delimited_rows("", '\t').is_empty());
+    }
+
+    #[test]
+    fn json_documents_and_lines() {
+        assert_eq!(json_rows("[{\"a\": 1}, {\"a\": 2}]").unwrap().len(), 2);
+        assert_eq!(json_rows("{\"a\": {\"b\": 1}}").unwrap(), vec![json!({"a": {"b": 1}})]);
+        assert_eq!(json_rows("{\"a\": 1}\n\n{\"a\": 2}\n").unwrap(), vec![json!({"a": 1}), json!({"a": 2})]);
+        assert!(json_rows("{\"a\": 1}\nnot json\n").is_err());
+    }
+
+    #[test]
+    fn parquet_rows_feed_queries() {
+        use parquet::data_type::{ByteArray, ByteArrayType, Int32Type};
+        use parquet::file::writer::SerializedFileWriter;
+        use parquet::schema::parser::parse_message_type;
+
+        let schema = parse_message_type("message m { required int32 id; optional binary name (UTF8); }").unwrap();
+        let mut buf = Vec::new();
+        let mut writer = SerializedFileWriter::new(&mut buf, std::sync::Arc::new(schema), Default::default()).unwrap();
+        let mut group = writer.next_row_group().unwrap();
+        let mut col = group.next_column().unwrap().unwrap();
+        col.typed::<Int32Type>().write_batch(&[1, 2, 3], None, None).unwrap();
+        col.close().unwrap();
+        let mut col = group.next_column().unwrap().unwrap();
+        col.typed::<ByteArrayType>()
+            .write_batch(&[ByteArray::from("ann"), ByteArray::from("bob")], Some(&[1, 0, 1]), None)
+            .unwrap();
+        col.close().unwrap();
+        group.close().unwrap();
+        writer.close().unwrap();
+
+        let rows = parquet_rows(bytes::Bytes::from(buf)).unwrap();
+        assert_eq!(rows[0], json!({"id": 1, "name": "ann"}));
+        assert_eq!(rows[1], json!({"id": 2, "name": null}));
+        assert_eq!(run("SELECT s.name FROM S3Object s WHERE s.id > 1", rows), vec![json!({"name": null}), json!({"name": "bob"})]);
+        assert!(parquet_rows(bytes::Bytes::from_static(b"not parquet")).is_err());
+    }
+}
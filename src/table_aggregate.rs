@@ -0,0 +1,399 @@
+// Counting and simple aggregation over a table (`count`, `aggregate`).
+//
+// `--where` conditions are compiled into expressions: when they pin the hash
+// key with `=`, the work is a Query (the range-key condition, or a `>=`/`<=`
+// pair as BETWEEN, goes into the key condition; other conditions on key
+// attributes are rejected, since a Query can't filter on them); otherwise it
+// is a Scan split into parallel segments. Counting
+// uses Select=COUNT so no items are transferred; aggregation projects only
+// the attributes it needs.
+
+use crate::dynamodb::{attribute_text, number_text, parse_typed_value};
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::types::{AttributeValue, KeyType, ScalarAttributeType, Select};
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Item = HashMap<String, AttributeValue>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    BeginsWith,
+    Exists,
+    NotExists,
+}
+
+/// One `--where` condition: `attr=value`, `attr!=value`, `attr<value`,
+/// `attr<=value`, `attr>value`, `attr>=value`, `attr^=prefix` (begins with),
+/// `attr?` (exists) or `!attr` (missing). Values may carry a type prefix
+/// (`S:123`); key attributes take their type from the table definition.
+pub struct Condition {
+    attr: String,
+    op: Op,
+    value: String,
+}
+
+impl std::str::FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Some(attr) = s.strip_suffix('?').filter(|a| !a.contains(['=', '<', '>'])) {
+            return Ok(Condition { attr: attr.trim().to_string(), op: Op::Exists, value: String::new() });
+        }
+        if let Some(attr) = s.strip_prefix('!').filter(|a| !a.contains(['=', '<', '>'])) {
+            return Ok(Condition { attr: attr.trim().to_string(), op: Op::NotExists, value: String::new() });
+        }
+        // Earliest operator wins; at the same position the longer one does.
+        const OPS: [(&str, Op); 7] =
+            [("!=", Op::Ne), ("<=", Op::Le), (">=", Op::Ge), ("^=", Op::BeginsWith), ("=", Op::Eq), ("<", Op::Lt), (">", Op::Gt)];
+        let (pos, token, op) = OPS
+            .iter()
+            .filter_map(|(t, op)| s.find(t).map(|p| (p, *t, *op)))
+            .min_by_key(|(p, t, _)| (*p, usize::MAX - t.len()))
+            .ok_or_else(|| format!("invalid condition '{}' (use e.g. a=1, a!=x, a>5, a^=pre, a? or !a)", s))?;
+        let attr = s[..pos].trim();
+        if attr.is_empty() {
+            return Err(format!("condition '{}' has no attribute name", s));
+        }
+        Ok(Condition { attr: attr.to_string(), op, value: s[pos + token.len()..].trim().to_string() })
+    }
+}
+
+fn query_key_error(attr: &str) -> String {
+    format!(
+        "unsupported condition on key attribute '{}': a Query takes one '=' on the hash key and one sort-key condition \
+         (=, <, <=, >, >=, ^=, or a >= and <= pair)",
+        attr
+    )
+}
+
+/// How to read the matching items: a Query on the hash key or a Scan.
+pub struct Selection {
+    table: String,
+    key_condition: Option<String>,
+    filter: Option<String>,
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl Selection {
+    /// Compile `conditions` against the key schema of `table`.
+    pub async fn new(client: &DdbClient, table: &str, conditions: &[Condition]) -> Result<Self, BoxError> {
+        let resp = client.describe_table().table_name(table).send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+        let desc = resp.table().ok_or_else(|| format!("table '{}' has no description", table))?;
+        let key_of = |kt: KeyType| desc.key_schema().iter().find(|k| k.key_type() == &kt).map(|k| k.attribute_name().to_string());
+        let (hash_key, range_key) = (key_of(KeyType::Hash), key_of(KeyType::Range));
+        let key_types: HashMap<&str, &ScalarAttributeType> =
+            desc.attribute_definitions().iter().map(|a| (a.attribute_name(), a.attribute_type())).collect();
+
+        let mut sel = Selection { table: table.to_string(), key_condition: None, filter: None, names: HashMap::new(), values: HashMap::new() };
+        let pins_hash = |c: &Condition| Some(&c.attr) == hash_key.as_ref() && c.op == Op::Eq;
+        let query = conditions.iter().any(pins_hash);
+        let (mut key_parts, mut filter_parts) = (Vec::new(), Vec::new());
+        let mut hash_used = false;
+        let mut range_parts: Vec<(Op, String, String, String)> = Vec::new();
+        for (i, c) in conditions.iter().enumerate() {
+            let name = format!("#w{}", i);
+            let value = format!(":w{}", i);
+            sel.names.insert(name.clone(), c.attr.clone());
+            if !matches!(c.op, Op::Exists | Op::NotExists) {
+                // Key attributes have a declared type; a bare `123` for an S key must stay a string.
+                let typed = match key_types.get(c.attr.as_str()) {
+                    Some(ScalarAttributeType::S) if !c.value.contains(':') => AttributeValue::S(c.value.clone()),
+                    Some(ScalarAttributeType::N) if !c.value.contains(':') => parse_typed_value(&format!("N:{}", c.value))?,
+                    _ => parse_typed_value(&c.value)?,
+                };
+                sel.values.insert(value.clone(), typed);
+            }
+            let expr = match c.op {
+                Op::Eq => format!("{} = {}", name, value),
+                Op::Ne => format!("{} <> {}", name, value),
+                Op::Lt => format!("{} < {}", name, value),
+                Op::Le => format!("{} <= {}", name, value),
+                Op::Gt => format!("{} > {}", name, value),
+                Op::Ge => format!("{} >= {}", name, value),
+                Op::BeginsWith => format!("begins_with({}, {})", name, value),
+                Op::Exists => format!("attribute_exists({})", name),
+                Op::NotExists => format!("attribute_not_exists({})", name),
+            };
+            let is_key = Some(&c.attr) == hash_key.as_ref() || Some(&c.attr) == range_key.as_ref();
+            let is_range = Some(&c.attr) == range_key.as_ref() && !matches!(c.op, Op::Ne | Op::Exists | Op::NotExists);
+            if !query || !is_key {
+                filter_parts.push(expr);
+            } else if pins_hash(c) && !hash_used {
+                hash_used = true;
+                key_parts.push(expr);
+            } else if is_range && range_parts.len() < 2 {
+                range_parts.push((c.op, expr, name, value));
+            } else {
+                // A Query's filter can't refer to key attributes.
+                return Err(query_key_error(&c.attr).into());
+            }
+        }
+        match range_parts.as_slice() {
+            [] => {}
+            [(_, expr, _, _)] => key_parts.push(expr.clone()),
+            // `sk>=a` and `sk<=b` together are `sk BETWEEN a AND b`.
+            [(Op::Ge, _, name, low), (Op::Le, _, other, high)] | [(Op::Le, _, other, high), (Op::Ge, _, name, low)] => {
+                sel.names.remove(other);
+                key_parts.push(format!("{} BETWEEN {} AND {}", name, low, high));
+            }
+            _ => return Err(query_key_error(range_key.as_deref().unwrap_or_default()).into()),
+        }
+        sel.key_condition = (!key_parts.is_empty()).then(|| key_parts.join(" AND "));
+        sel.filter = (!filter_parts.is_empty()).then(|| filter_parts.join(" AND "));
+        Ok(sel)
+    }
+
+    pub fn is_query(&self) -> bool {
+        self.key_condition.is_some()
+    }
+
+    /// Read every matching page, calling `on_page(count, scanned_count, items)`.
+    /// Scans run `segments` workers in parallel; queries can't be split.
+    async fn for_each_page<F>(&self, client: &DdbClient, segments: usize, count_only: bool, projection: &[String], on_page: &F) -> Result<(), BoxError>
+    where
+        F: Fn(u64, u64, Vec<Item>) + Sync,
+    {
+        let mut names = self.names.clone();
+        let projection_expr = (!projection.is_empty()).then(|| {
+            projection
+                .iter()
+                .enumerate()
+                .map(|(i, a)| {
+                    names.insert(format!("#p{}", i), a.clone());
+                    format!("#p{}", i)
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        });
+        let names = (!names.is_empty()).then_some(names);
+        let values = (!self.values.is_empty()).then(|| self.values.clone());
+        let select = if count_only { Select::Count } else { Select::AllAttributes };
+        let projection_expr = if count_only { None } else { projection_expr };
+
+        if let Some(key_condition) = &self.key_condition {
+            let mut start_key = None;
+            loop {
+                let page = client
+                    .query()
+                    .table_name(&self.table)
+                    .key_condition_expression(key_condition)
+                    .set_filter_expression(self.filter.clone())
+                    .set_expression_attribute_names(names.clone())
+                    .set_expression_attribute_values(values.clone())
+                    .set_projection_expression(projection_expr.clone())
+                    .set_select(projection_expr.is_none().then(|| select.clone()))
+                    .set_exclusive_start_key(start_key)
+                    .send()
+                    .await
+                    .map_err(aws_sdk_dynamodb::Error::from)?;
+                on_page(page.count as u64, page.scanned_count as u64, page.items.unwrap_or_default());
+                start_key = page.last_evaluated_key;
+                if start_key.is_none() {
+                    return Ok(());
+                }
+            }
+        }
+
+        let scan_segment = |segment: usize| {
+            let (names, values, projection_expr, select) = (names.clone(), values.clone(), projection_expr.clone(), select.clone());
+            async move {
+                let mut start_key = None;
+                loop {
+                    let page = client
+                        .scan()
+                        .table_name(&self.table)
+                        .segment(segment as i32)
+                        .total_segments(segments as i32)
+                        .set_filter_expression(self.filter.clone())
+                        .set_expression_attribute_names(names.clone())
+                        .set_expression_attribute_values(values.clone())
+                        .set_projection_expression(projection_expr.clone())
+                        .set_select(projection_expr.is_none().then(|| select.clone()))
+                        .set_exclusive_start_key(start_key)
+                        .send()
+                        .await
+                        .map_err(aws_sdk_dynamodb::Error::from)?;
+                    on_page(page.count as u64, page.scanned_count as u64, page.items.unwrap_or_default());
+                    start_key = page.last_evaluated_key;
+                    if start_key.is_none() {
+                        return Ok::<(), BoxError>(());
+                    }
+                }
+            }
+        };
+        futures::future::try_join_all((0..segments).map(scan_segment)).await?;
+        Ok(())
+    }
+}
+
+pub struct CountResult {
+    pub count: u64,
+    pub scanned: u64,
+}
+
+pub async fn count_items(client: &DdbClient, sel: &Selection, segments: usize) -> Result<CountResult, BoxError> {
+    let totals = Mutex::new(CountResult { count: 0, scanned: 0 });
+    sel.for_each_page(client, segments, true, &[], &|count, scanned, _| {
+        let mut t = totals.lock().unwrap();
+        t.count += count;
+        t.scanned += scanned;
+    })
+    .await?;
+    Ok(totals.into_inner().unwrap())
+}
+
+#[derive(Default)]
+struct GroupStats {
+    items: u64,
+    numbers: u64,
+    non_numeric: u64,
+    sum: f64,
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl GroupStats {
+    fn add(&mut self, value: Option<&AttributeValue>) {
+        self.items += 1;
+        match value {
+            Some(AttributeValue::N(n)) => match n.parse::<f64>() {
+                Ok(x) => {
+                    self.numbers += 1;
+                    self.sum += x;
+                    self.min = Some(self.min.map_or(x, |m| m.min(x)));
+                    self.max = Some(self.max.map_or(x, |m| m.max(x)));
+                }
+                Err(_) => self.non_numeric += 1,
+            },
+            Some(_) => self.non_numeric += 1,
+            None => {}
+        }
+    }
+
+    fn avg(&self) -> Option<f64> {
+        (self.numbers > 0).then(|| self.sum / self.numbers as f64)
+    }
+}
+
+/// Label for a group-by value; items without the attribute form their own group.
+const MISSING_GROUP: &str = "(missing)";
+
+pub struct Aggregation {
+    attribute: Option<String>,
+    group_by: Option<String>,
+    groups: BTreeMap<String, GroupStats>,
+    scanned: u64,
+}
+
+/// Sum/min/max/avg of the numeric `attribute` and/or item counts per value of
+/// `group_by`, over the items matching `sel`.
+pub async fn aggregate(
+    client: &DdbClient,
+    sel: &Selection,
+    attribute: Option<&str>,
+    group_by: Option<&str>,
+    segments: usize,
+) -> Result<Aggregation, BoxError> {
+    let projection: Vec<String> = attribute.iter().chain(group_by.iter()).map(|a| a.to_string()).collect();
+    let state = Mutex::new((BTreeMap::<String, GroupStats>::new(), 0u64));
+    sel.for_each_page(client, segments, false, &projection, &|_, scanned, items| {
+        let mut guard = state.lock().unwrap();
+        let (groups, total_scanned) = &mut *guard;
+        *total_scanned += scanned;
+        for item in &items {
+            let group = match group_by {
+                Some(g) => item.get(g).map(attribute_text).unwrap_or_else(|| MISSING_GROUP.to_string()),
+                None => String::new(),
+            };
+            groups.entry(group).or_default().add(attribute.and_then(|a| item.get(a)));
+        }
+    })
+    .await?;
+    let (groups, scanned) = state.into_inner().unwrap();
+    Ok(Aggregation { attribute: attribute.map(|a| a.to_string()), group_by: group_by.map(|g| g.to_string()), groups, scanned })
+}
+
+impl Aggregation {
+    /// Groups, largest first.
+    fn sorted(&self) -> Vec<(&String, &GroupStats)> {
+        let mut groups: Vec<_> = self.groups.iter().collect();
+        groups.sort_by(|a, b| b.1.items.cmp(&a.1.items).then(a.0.cmp(b.0)));
+        groups
+    }
+
+    fn stats_json(&self, g: &GroupStats) -> Value {
+        let mut doc = json!({ "Count": g.items });
+        if self.attribute.is_some() {
+            doc["Numbers"] = json!(g.numbers);
+            doc["Sum"] = json!(g.sum);
+            doc["Min"] = json!(g.min);
+            doc["Max"] = json!(g.max);
+            doc["Avg"] = json!(g.avg());
+        }
+        doc
+    }
+
+    pub fn to_json(&self) -> Value {
+        let mut doc = json!({ "Attribute": self.attribute, "GroupBy": self.group_by, "ScannedCount": self.scanned });
+        if self.group_by.is_some() {
+            doc["Groups"] = self
+                .sorted()
+                .into_iter()
+                .map(|(name, g)| {
+                    let mut v = self.stats_json(g);
+                    v["Group"] = json!(name);
+                    v
+                })
+                .collect();
+        } else {
+            let empty = GroupStats::default();
+            let all = self.groups.get("").unwrap_or(&empty);
+            doc["Result"] = self.stats_json(all);
+        }
+        doc
+    }
+
+    pub fn print_text(&self) {
+        let opt = |x: Option<f64>| x.map(number_text).unwrap_or_else(|| "-".to_string());
+        let non_numeric: u64 = self.groups.values().map(|g| g.non_numeric).sum();
+        if self.group_by.is_some() {
+            if self.attribute.is_some() {
+                println!("GROUP\tCOUNT\tSUM\tMIN\tMAX\tAVG");
+            } else {
+                println!("GROUP\tCOUNT");
+            }
+            for (name, g) in self.sorted() {
+                if self.attribute.is_some() {
+                    println!("{}\t{}\t{}\t{}\t{}\t{}", name, g.items, number_text(g.sum), opt(g.min), opt(g.max), opt(g.avg()));
+                } else {
+                    println!("{}\t{}", name, g.items);
+                }
+            }
+        } else {
+            let empty = GroupStats::default();
+            let g = self.groups.get("").unwrap_or(&empty);
+            println!("count: {}", g.items);
+            if let Some(attr) = &self.attribute {
+                println!("{} present (numeric): {}", attr, g.numbers);
+                println!("sum: {}", number_text(g.sum));
+                println!("min: {}", opt(g.min));
+                println!("max: {}", opt(g.max));
+                println!("avg: {}", opt(g.avg()));
+            }
+        }
+        if non_numeric > 0 {
+            eprintln!("note: {} non-numeric value(s) of {} ignored", non_numeric, self.attribute.as_deref().unwrap_or_default());
+        }
+    }
+}
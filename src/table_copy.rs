@@ -0,0 +1,257 @@
+// Copy the items of one DynamoDB table into another (`copy-table`), possibly
+// in a different region or account.
+//
+// The source is scanned in parallel segments; each page is transformed and
+// written with BatchWriteItem (25 items per request, unprocessed items retried
+// with backoff). With a checkpoint file, every segment's LastEvaluatedKey is
+// saved after its page has been written, so an interrupted copy resumes from
+// there; items of a partly written page are simply put again. Items are
+// rewritten on the way with the `item_transform` rules.
+
+use crate::dynamodb::{item_from_json, item_to_json};
+use crate::item_transform::Transform;
+use crate::table_schema;
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::types::{AttributeValue, PutRequest, WriteRequest};
+use futures::{StreamExt, TryStreamExt};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Item = HashMap<String, AttributeValue>;
+
+/// BatchWriteItem accepts at most 25 requests.
+const BATCH_SIZE: usize = 25;
+/// Batches each segment keeps in flight.
+const BATCHES_IN_FLIGHT: usize = 4;
+/// Attempts at writing a batch's unprocessed items before giving up.
+const MAX_BATCH_ATTEMPTS: u32 = 10;
+/// Print a progress line every this many items.
+const PROGRESS_EVERY: u64 = 10_000;
+
+/// Build a client for the destination from an explicit profile, region and
+/// endpoint; unset parts come from the usual environment / config files.
+pub async fn destination_client(profile: Option<&str>, region: Option<&str>, endpoint: Option<&str>) -> DdbClient {
+    let mut loader = aws_config::defaults(aws_config::BehaviorVersion::latest());
+    if let Some(p) = profile {
+        loader = loader.profile_name(p);
+    }
+    if let Some(r) = region {
+        loader = loader.region(aws_config::Region::new(r.to_string()));
+    }
+    if let Some(e) = endpoint {
+        loader = loader.endpoint_url(e);
+    }
+    DdbClient::new(&loader.load().await)
+}
+
+#[derive(Clone, Default)]
+struct SegmentState {
+    done: bool,
+    last_key: Option<Item>,
+    copied: u64,
+}
+
+/// Progress of every scan segment, persisted to `path` (if any) as JSON.
+struct Checkpoint {
+    path: Option<String>,
+    source: String,
+    destination: String,
+    segments: Vec<SegmentState>,
+}
+
+impl Checkpoint {
+    /// Resume from `path` if it exists (it must describe the same copy), else start fresh.
+    fn open(path: Option<&str>, source: &str, destination: &str, segments: usize) -> Result<Self, BoxError> {
+        let fresh = Checkpoint {
+            path: path.map(|p| p.to_string()),
+            source: source.to_string(),
+            destination: destination.to_string(),
+            segments: vec![SegmentState::default(); segments],
+        };
+        let Some(path) = path else { return Ok(fresh) };
+        let text = match std::fs::read_to_string(path) {
+            Ok(t) => t,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(fresh),
+            Err(e) => return Err(e.into()),
+        };
+        let doc: Value = serde_json::from_str(&text)?;
+        if doc["Source"] != json!(source) || doc["Destination"] != json!(destination) {
+            return Err(format!("checkpoint {} is for a different copy ({} -> {})", path, doc["Source"], doc["Destination"]).into());
+        }
+        let saved = doc["Segments"].as_array().ok_or("checkpoint has no Segments")?;
+        if saved.len() != segments {
+            return Err(format!("checkpoint {} was written with --segments {}", path, saved.len()).into());
+        }
+        let segments = saved
+            .iter()
+            .map(|s| {
+                Ok(SegmentState {
+                    done: s["Done"].as_bool().unwrap_or(false),
+                    last_key: s.get("LastEvaluatedKey").filter(|k| !k.is_null()).map(item_from_json).transpose()?,
+                    copied: s["Copied"].as_u64().unwrap_or(0),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Checkpoint { segments, ..fresh })
+    }
+
+    /// Write the checkpoint atomically (temp file + rename).
+    fn save(&self) -> std::io::Result<()> {
+        let Some(path) = &self.path else { return Ok(()) };
+        let doc = json!({
+            "Source": self.source,
+            "Destination": self.destination,
+            "Segments": self.segments.iter().map(|s| json!({
+                "Done": s.done,
+                "LastEvaluatedKey": s.last_key.as_ref().map(item_to_json),
+                "Copied": s.copied,
+            })).collect::<Vec<_>>(),
+        });
+        let tmp = format!("{}.tmp", path);
+        std::fs::write(&tmp, format!("{:#}\n", doc))?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+pub struct CopyOptions<'a> {
+    pub segments: usize,
+    pub transforms: &'a [Transform],
+    pub checkpoint: Option<&'a str>,
+}
+
+pub struct CopyStats {
+    /// Items written in this run.
+    pub copied: u64,
+    /// Items written by earlier runs, according to the checkpoint.
+    pub resumed: u64,
+}
+
+/// Write `items` with BatchWriteItem, retrying unprocessed items with exponential backoff.
+async fn write_batch(client: &DdbClient, table: &str, items: Vec<Item>) -> Result<(), BoxError> {
+    let mut requests: Vec<WriteRequest> = items
+        .into_iter()
+        .map(|item| Ok(WriteRequest::builder().put_request(PutRequest::builder().set_item(Some(item)).build()?).build()))
+        .collect::<Result<_, BoxError>>()?;
+    let mut delay = Duration::from_millis(50);
+    for _ in 0..MAX_BATCH_ATTEMPTS {
+        let resp = client
+            .batch_write_item()
+            .request_items(table, requests)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        requests = resp.unprocessed_items.and_then(|mut u| u.remove(table)).unwrap_or_default();
+        if requests.is_empty() {
+            return Ok(());
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(Duration::from_secs(5));
+    }
+    Err(format!("{} item(s) still unprocessed after {} attempts", requests.len(), MAX_BATCH_ATTEMPTS).into())
+}
+
+#[allow(clippy::too_many_arguments)]
+async fn copy_segment(
+    src: &DdbClient,
+    dst: &DdbClient,
+    source: &str,
+    destination: &str,
+    segment: usize,
+    opts: &CopyOptions<'_>,
+    checkpoint: &Mutex<Checkpoint>,
+    total: &AtomicU64,
+) -> Result<(), BoxError> {
+    let mut start_key = {
+        let cp = checkpoint.lock().unwrap();
+        if cp.segments[segment].done {
+            return Ok(());
+        }
+        cp.segments[segment].last_key.clone()
+    };
+    loop {
+        let page = src
+            .scan()
+            .table_name(source)
+            .segment(segment as i32)
+            .total_segments(opts.segments as i32)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await
+            .map_err(aws_sdk_dynamodb::Error::from)?;
+        let mut items = page.items.unwrap_or_default();
+        for item in &mut items {
+            for t in opts.transforms {
+                t.apply(item).map_err(|e| format!("item {}: {}", item_to_json(item), e))?;
+            }
+        }
+        let count = items.len() as u64;
+        let mut batches = Vec::new();
+        while !items.is_empty() {
+            let rest = items.split_off(items.len().min(BATCH_SIZE));
+            batches.push(std::mem::replace(&mut items, rest));
+        }
+        futures::stream::iter(batches)
+            .map(|batch| write_batch(dst, destination, batch))
+            .buffer_unordered(BATCHES_IN_FLIGHT)
+            .try_collect::<Vec<()>>()
+            .await?;
+
+        start_key = page.last_evaluated_key;
+        {
+            let mut cp = checkpoint.lock().unwrap();
+            let state = &mut cp.segments[segment];
+            state.copied += count;
+            state.last_key = start_key.clone();
+            state.done = start_key.is_none();
+            cp.save()?;
+        }
+        let before = total.fetch_add(count, Ordering::Relaxed);
+        if (before + count) / PROGRESS_EVERY > before / PROGRESS_EVERY {
+            eprintln!("{} item(s) copied...", before + count);
+        }
+        if start_key.is_none() {
+            return Ok(());
+        }
+    }
+}
+
+/// Create `destination` (via `dst`) from the schema of `source` unless it
+/// already exists; returns whether it was created.
+pub async fn ensure_destination(src: &DdbClient, dst: &DdbClient, source: &str, destination: &str) -> Result<bool, BoxError> {
+    match dst.describe_table().table_name(destination).send().await.map_err(aws_sdk_dynamodb::Error::from) {
+        Ok(_) => return Ok(false),
+        Err(aws_sdk_dynamodb::Error::ResourceNotFoundException(_)) => {}
+        Err(e) => return Err(e.into()),
+    }
+    let mut schema = table_schema::schema_from_table(src, source).await?;
+    schema.table_name = destination.to_string();
+    table_schema::create_table(dst, &schema, Some(table_schema::DEFAULT_WAIT_TIMEOUT)).await?;
+    Ok(true)
+}
+
+/// Copy every item of `source` (via `src`) into `destination` (via `dst`).
+pub async fn copy_table(
+    src: &DdbClient,
+    dst: &DdbClient,
+    source: &str,
+    destination: &str,
+    opts: &CopyOptions<'_>,
+) -> Result<CopyStats, BoxError> {
+    let checkpoint = Checkpoint::open(opts.checkpoint, source, destination, opts.segments)?;
+    let resumed: u64 = checkpoint.segments.iter().map(|s| s.copied).sum();
+    if resumed > 0 {
+        let done = checkpoint.segments.iter().filter(|s| s.done).count();
+        eprintln!("Resuming: {} item(s) already copied, {}/{} segment(s) complete", resumed, done, opts.segments);
+    }
+    let checkpoint = Mutex::new(checkpoint);
+    let total = AtomicU64::new(0);
+    futures::future::try_join_all(
+        (0..opts.segments).map(|segment| copy_segment(src, dst, source, destination, segment, opts, &checkpoint, &total)),
+    )
+    .await?;
+    Ok(CopyStats { copied: total.into_inner(), resumed })
+}
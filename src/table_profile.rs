@@ -0,0 +1,234 @@
+// Infer the de-facto schema of a table (`profile-table`): for every attribute
+// seen in a scan (or sample), its types, how often it is present, an estimate
+// of its distinct values, number ranges, string lengths and a few examples.
+//
+// Items are processed a page at a time, column by column like the CSV export,
+// so memory stays bounded regardless of table size.
+
+use crate::dynamodb::{attribute_text, attribute_type_name, collect_headers, number_text};
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Item = HashMap<String, AttributeValue>;
+
+/// Example values kept per attribute.
+const EXAMPLES: usize = 3;
+/// Examples longer than this are truncated.
+const EXAMPLE_WIDTH: usize = 40;
+/// log2 of the HyperLogLog register count (4096 registers, ~1.6% error).
+const HLL_BITS: u32 = 12;
+
+/// HyperLogLog distinct-count estimator.
+struct DistinctEstimator {
+    registers: Vec<u8>,
+}
+
+impl DistinctEstimator {
+    fn new() -> Self {
+        DistinctEstimator { registers: vec![0; 1 << HLL_BITS] }
+    }
+
+    fn insert(&mut self, value: &impl Hash) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let h = hasher.finish();
+        let index = (h >> (64 - HLL_BITS)) as usize;
+        let rank = ((h << HLL_BITS) | (1 << (HLL_BITS - 1))).leading_zeros() as u8 + 1;
+        self.registers[index] = self.registers[index].max(rank);
+    }
+
+    fn estimate(&self) -> u64 {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        // Small-range correction: linear counting is far more accurate here.
+        let estimate = if raw <= 2.5 * m && zeros > 0 { m * (m / zeros as f64).ln() } else { raw };
+        estimate.round() as u64
+    }
+}
+
+#[derive(Default)]
+struct MinMax {
+    min: Option<f64>,
+    max: Option<f64>,
+}
+
+impl MinMax {
+    fn add(&mut self, x: f64) {
+        self.min = Some(self.min.map_or(x, |m| m.min(x)));
+        self.max = Some(self.max.map_or(x, |m| m.max(x)));
+    }
+}
+
+struct AttributeProfile {
+    present: u64,
+    types: BTreeMap<&'static str, u64>,
+    distinct: DistinctEstimator,
+    numbers: MinMax,
+    lengths: MinMax,
+    length_total: u64,
+    strings: u64,
+    examples: Vec<String>,
+}
+
+impl AttributeProfile {
+    fn new() -> Self {
+        AttributeProfile {
+            present: 0,
+            types: BTreeMap::new(),
+            distinct: DistinctEstimator::new(),
+            numbers: MinMax::default(),
+            lengths: MinMax::default(),
+            length_total: 0,
+            strings: 0,
+            examples: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, v: &AttributeValue) {
+        self.present += 1;
+        *self.types.entry(attribute_type_name(v)).or_default() += 1;
+        self.distinct.insert(&format!("{:?}", v));
+        match v {
+            AttributeValue::N(n) => {
+                if let Ok(x) = n.parse::<f64>() {
+                    self.numbers.add(x);
+                }
+            }
+            AttributeValue::S(s) => {
+                let len = s.chars().count();
+                self.lengths.add(len as f64);
+                self.length_total += len as u64;
+                self.strings += 1;
+            }
+            _ => {}
+        }
+        if self.examples.len() < EXAMPLES {
+            let text = example_text(v);
+            if !self.examples.contains(&text) {
+                self.examples.push(text);
+            }
+        }
+    }
+}
+
+fn example_text(v: &AttributeValue) -> String {
+    let text = attribute_text(v);
+    if text.chars().count() > EXAMPLE_WIDTH {
+        format!("{}...", text.chars().take(EXAMPLE_WIDTH).collect::<String>())
+    } else {
+        text
+    }
+}
+
+pub struct TableProfile {
+    items: u64,
+    /// Whether the scan stopped early because of the sample size.
+    sampled: bool,
+    attributes: BTreeMap<String, AttributeProfile>,
+}
+
+impl TableProfile {
+    fn add_page(&mut self, items: &[Item]) {
+        self.items += items.len() as u64;
+        for name in collect_headers(items) {
+            let profile = self.attributes.entry(name.clone()).or_insert_with(AttributeProfile::new);
+            for v in items.iter().filter_map(|it| it.get(&name)) {
+                profile.add(v);
+            }
+        }
+    }
+
+    fn percent(&self, n: u64) -> f64 {
+        if self.items == 0 { 0.0 } else { n as f64 * 100.0 / self.items as f64 }
+    }
+
+    pub fn to_json(&self, table: &str) -> Value {
+        let attributes: Vec<Value> = self
+            .attributes
+            .iter()
+            .map(|(name, a)| {
+                let mut doc = json!({
+                    "AttributeName": name,
+                    "Present": a.present,
+                    "PresentPercent": (self.percent(a.present) * 10.0).round() / 10.0,
+                    "Types": a.types,
+                    "DistinctEstimate": a.distinct.estimate().min(a.present),
+                    "Examples": a.examples,
+                });
+                if let (Some(min), Some(max)) = (a.numbers.min, a.numbers.max) {
+                    doc["NumberRange"] = json!({ "Min": min, "Max": max });
+                }
+                if let (Some(min), Some(max)) = (a.lengths.min, a.lengths.max) {
+                    doc["StringLength"] = json!({ "Min": min as u64, "Max": max as u64, "Avg": a.length_total as f64 / a.strings as f64 });
+                }
+                doc
+            })
+            .collect();
+        json!({ "TableName": table, "ItemsScanned": self.items, "Sampled": self.sampled, "Attributes": attributes })
+    }
+
+    pub fn print_text(&self, table: &str) {
+        println!(
+            "{}: {} item(s) {}, {} attribute(s)\n",
+            table,
+            self.items,
+            if self.sampled { "sampled" } else { "scanned" },
+            self.attributes.len()
+        );
+        println!("ATTRIBUTE\tTYPES\tPRESENT\tDISTINCT~\tNUMBERS\tLENGTH(min/avg/max)\tEXAMPLES");
+        for (name, a) in &self.attributes {
+            let types = a
+                .types
+                .iter()
+                .map(|(t, n)| if a.types.len() == 1 { t.to_string() } else { format!("{} {:.0}%", t, *n as f64 * 100.0 / a.present as f64) })
+                .collect::<Vec<_>>()
+                .join(", ");
+            let numbers = match (a.numbers.min, a.numbers.max) {
+                (Some(min), Some(max)) => format!("{}..{}", number_text(min), number_text(max)),
+                _ => "-".to_string(),
+            };
+            let lengths = match (a.lengths.min, a.lengths.max) {
+                (Some(min), Some(max)) => format!("{}/{:.1}/{}", min, a.length_total as f64 / a.strings as f64, max),
+                _ => "-".to_string(),
+            };
+            println!(
+                "{}\t{}\t{:.1}%\t{}\t{}\t{}\t{}",
+                name,
+                types,
+                self.percent(a.present),
+                a.distinct.estimate().min(a.present),
+                numbers,
+                lengths,
+                a.examples.join(" | ")
+            );
+        }
+    }
+}
+
+/// Scan `table` (stopping after about `sample` items, if given) and profile its attributes.
+pub async fn profile_table(client: &DdbClient, table: &str, sample: Option<u64>) -> Result<TableProfile, BoxError> {
+    let mut profile = TableProfile { items: 0, sampled: false, attributes: BTreeMap::new() };
+    let mut pages = client.scan().table_name(table).into_paginator().send();
+    while let Some(page) = pages.next().await {
+        let page = page.map_err(aws_sdk_dynamodb::Error::from)?;
+        let mut items = page.items.unwrap_or_default();
+        if let Some(limit) = sample {
+            let room = limit.saturating_sub(profile.items) as usize;
+            if items.len() >= room {
+                profile.sampled = items.len() > room || page.last_evaluated_key.is_some();
+                items.truncate(room);
+                profile.add_page(&items);
+                break;
+            }
+        }
+        profile.add_page(&items);
+    }
+    Ok(profile)
+}
@@ -0,0 +1,627 @@
+// Declarative DynamoDB table schemas for `create-table --from` and
+// `describe-table --schema`.
+//
+// Files use the request shape of `aws dynamodb create-table --cli-input-json`
+// (TableName, AttributeDefinitions, KeySchema, GlobalSecondaryIndexes,
+// LocalSecondaryIndexes, BillingMode, ProvisionedThroughput,
+// StreamSpecification, Tags) plus a `TimeToLive: {AttributeName, Enabled}`
+// section, as JSON or YAML. Emitted schemas are normalized (lists sorted by
+// name) so they diff cleanly in version control.
+//
+// `table-diff` compares two schemas and can apply the subset of drift that
+// UpdateTable / UpdateTimeToLive handle in place (see `plan_changes`).
+
+use crate::dynamodb::list_table_tags;
+use crate::json_doc::{bool_field, field, i64_field, list_field, object, str_field, strings_field};
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, BillingMode, CreateGlobalSecondaryIndexAction, DeleteGlobalSecondaryIndexAction,
+    GlobalSecondaryIndex, GlobalSecondaryIndexUpdate, IndexStatus, KeySchemaElement, LocalSecondaryIndex, Projection,
+    ProjectionType, ProvisionedThroughput, StreamSpecification, TableDescription, TableStatus, Tag,
+    TimeToLiveSpecification, TimeToLiveStatus, UpdateGlobalSecondaryIndexAction,
+};
+use serde_json::{Value, json};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// How often to poll DescribeTable while waiting for a table to become ACTIVE.
+const WAIT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// How long to wait for ACTIVE unless `--wait-timeout` says otherwise; GSI
+/// backfills on large tables can take a while.
+pub const DEFAULT_WAIT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+pub struct TableSchema {
+    pub table_name: String,
+    pub attribute_definitions: Vec<AttributeDefinition>,
+    pub key_schema: Vec<KeySchemaElement>,
+    pub global_indexes: Vec<GlobalSecondaryIndex>,
+    pub local_indexes: Vec<LocalSecondaryIndex>,
+    pub billing_mode: BillingMode,
+    pub throughput: Option<ProvisionedThroughput>,
+    pub stream: Option<StreamSpecification>,
+    /// TTL attribute, when TTL is enabled.
+    pub ttl_attribute: Option<String>,
+    pub tags: Vec<Tag>,
+}
+
+// ---- JSON helpers ----
+
+/// A string field that must be one of `allowed` (case-insensitive).
+fn enum_field<T: for<'a> From<&'a str>>(v: &Value, name: &str, allowed: &[&str]) -> Result<Option<T>, BoxError> {
+    match str_field(v, name)? {
+        None => Ok(None),
+        Some(s) => match allowed.iter().find(|a| a.eq_ignore_ascii_case(&s)) {
+            Some(a) => Ok(Some(T::from(a))),
+            None => Err(format!("invalid {} '{}' (expected one of {})", name, s, allowed.join(", ")).into()),
+        },
+    }
+}
+
+fn key_schema_from_json(list: &[Value]) -> Result<Vec<KeySchemaElement>, BoxError> {
+    list.iter()
+        .map(|k| {
+            Ok(KeySchemaElement::builder()
+                .set_attribute_name(str_field(k, "AttributeName")?)
+                .set_key_type(enum_field(k, "KeyType", &["HASH", "RANGE"])?)
+                .build()?)
+        })
+        .collect()
+}
+
+fn key_schema_to_json(keys: &[KeySchemaElement]) -> Value {
+    keys.iter()
+        .map(|k| json!({ "AttributeName": k.attribute_name(), "KeyType": k.key_type().as_str() }))
+        .collect()
+}
+
+fn projection_from_json(v: Option<&Value>) -> Result<Projection, BoxError> {
+    let Some(v) = v else {
+        return Ok(Projection::builder().projection_type(ProjectionType::All).build());
+    };
+    Ok(Projection::builder()
+        .set_projection_type(enum_field(v, "ProjectionType", &["ALL", "KEYS_ONLY", "INCLUDE"])?)
+        .set_non_key_attributes(strings_field(v, "NonKeyAttributes")?)
+        .build())
+}
+
+fn projection_to_json(p: Option<&Projection>) -> Value {
+    let Some(p) = p else { return Value::Null };
+    let mut non_key = p.non_key_attributes().to_vec();
+    non_key.sort();
+    object(vec![
+        ("ProjectionType", json!(p.projection_type().map(|t| t.as_str()))),
+        ("NonKeyAttributes", json!(non_key)),
+    ])
+}
+
+fn throughput_from_json(v: Option<&Value>) -> Result<Option<ProvisionedThroughput>, BoxError> {
+    let Some(v) = v else { return Ok(None) };
+    Ok(Some(
+        ProvisionedThroughput::builder()
+            .set_read_capacity_units(i64_field(v, "ReadCapacityUnits")?)
+            .set_write_capacity_units(i64_field(v, "WriteCapacityUnits")?)
+            .build()?,
+    ))
+}
+
+fn throughput_to_json(p: Option<&ProvisionedThroughput>) -> Value {
+    match p {
+        Some(p) => json!({ "ReadCapacityUnits": p.read_capacity_units(), "WriteCapacityUnits": p.write_capacity_units() }),
+        None => Value::Null,
+    }
+}
+
+/// Provisioned throughput as reported by DescribeTable; on-demand tables report 0/0.
+fn described_throughput(read: Option<i64>, write: Option<i64>) -> Option<ProvisionedThroughput> {
+    match (read.unwrap_or(0), write.unwrap_or(0)) {
+        (0, 0) => None,
+        (r, w) => ProvisionedThroughput::builder().read_capacity_units(r).write_capacity_units(w).build().ok(),
+    }
+}
+
+impl TableSchema {
+    pub fn from_json(doc: &Value) -> Result<Self, BoxError> {
+        let table_name = str_field(doc, "TableName")?.ok_or("schema is missing TableName")?;
+        let billing_mode = enum_field(doc, "BillingMode", &["PROVISIONED", "PAY_PER_REQUEST"])?.unwrap_or(BillingMode::Provisioned);
+        let provisioned = billing_mode == BillingMode::Provisioned;
+        let throughput = throughput_from_json(field(doc, "ProvisionedThroughput"))?;
+        if provisioned && throughput.is_none() {
+            return Err("BillingMode PROVISIONED requires ProvisionedThroughput".into());
+        }
+
+        let attribute_definitions = list_field(doc, "AttributeDefinitions")?
+            .iter()
+            .map(|a| {
+                Ok(AttributeDefinition::builder()
+                    .set_attribute_name(str_field(a, "AttributeName")?)
+                    .set_attribute_type(enum_field(a, "AttributeType", &["S", "N", "B"])?)
+                    .build()?)
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        let key_schema = key_schema_from_json(list_field(doc, "KeySchema")?)?;
+        if key_schema.is_empty() {
+            return Err("schema is missing KeySchema".into());
+        }
+
+        let global_indexes = list_field(doc, "GlobalSecondaryIndexes")?
+            .iter()
+            .map(|g| {
+                let gsi_throughput = throughput_from_json(field(g, "ProvisionedThroughput"))?;
+                Ok(GlobalSecondaryIndex::builder()
+                    .set_index_name(str_field(g, "IndexName")?)
+                    .set_key_schema(Some(key_schema_from_json(list_field(g, "KeySchema")?)?))
+                    .projection(projection_from_json(field(g, "Projection"))?)
+                    // Index capacity defaults to the table's when provisioned.
+                    .set_provisioned_throughput(if provisioned { gsi_throughput.or_else(|| throughput.clone()) } else { None })
+                    .build()?)
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        let local_indexes = list_field(doc, "LocalSecondaryIndexes")?
+            .iter()
+            .map(|l| {
+                Ok(LocalSecondaryIndex::builder()
+                    .set_index_name(str_field(l, "IndexName")?)
+                    .set_key_schema(Some(key_schema_from_json(list_field(l, "KeySchema")?)?))
+                    .projection(projection_from_json(field(l, "Projection"))?)
+                    .build()?)
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+
+        let stream = match field(doc, "StreamSpecification") {
+            Some(s) if bool_field(s, "StreamEnabled")? == Some(true) => Some(
+                StreamSpecification::builder()
+                    .stream_enabled(true)
+                    .set_stream_view_type(enum_field(
+                        s,
+                        "StreamViewType",
+                        &["NEW_IMAGE", "OLD_IMAGE", "NEW_AND_OLD_IMAGES", "KEYS_ONLY"],
+                    )?)
+                    .build()?,
+            ),
+            _ => None,
+        };
+        let ttl_attribute = match field(doc, "TimeToLive") {
+            Some(t) if bool_field(t, "Enabled")?.unwrap_or(true) => str_field(t, "AttributeName")?,
+            _ => None,
+        };
+
+        // Tags may be `[{Key, Value}]` (CLI shape) or a plain `{key: value}` map.
+        let tags = match field(doc, "Tags") {
+            Some(Value::Object(m)) => m
+                .iter()
+                .map(|(k, v)| {
+                    let value = v.as_str().ok_or_else(|| format!("tag {} must have a string value", k))?;
+                    Ok(Tag::builder().key(k).value(value).build()?)
+                })
+                .collect::<Result<Vec<_>, BoxError>>()?,
+            _ => list_field(doc, "Tags")?
+                .iter()
+                .map(|t| Ok(Tag::builder().set_key(str_field(t, "Key")?).set_value(str_field(t, "Value")?).build()?))
+                .collect::<Result<Vec<_>, BoxError>>()?,
+        };
+
+        Ok(TableSchema {
+            table_name,
+            attribute_definitions,
+            key_schema,
+            global_indexes,
+            local_indexes,
+            throughput: if provisioned { throughput } else { None },
+            billing_mode,
+            stream,
+            ttl_attribute,
+            tags,
+        })
+    }
+
+    /// The schema of an existing table, from its description, TTL setting and tags.
+    pub fn from_description(t: &TableDescription, ttl_attribute: Option<String>, tags: Vec<(String, String)>) -> Result<Self, BoxError> {
+        let billing_mode = t.billing_mode_summary().and_then(|b| b.billing_mode()).cloned().unwrap_or(BillingMode::Provisioned);
+        let provisioned = billing_mode == BillingMode::Provisioned;
+        let throughput = t
+            .provisioned_throughput()
+            .and_then(|p| described_throughput(p.read_capacity_units(), p.write_capacity_units()))
+            .filter(|_| provisioned);
+        let global_indexes = t
+            .global_secondary_indexes()
+            .iter()
+            .map(|g| {
+                let gsi_throughput = g
+                    .provisioned_throughput()
+                    .and_then(|p| described_throughput(p.read_capacity_units(), p.write_capacity_units()))
+                    .filter(|_| provisioned);
+                Ok(GlobalSecondaryIndex::builder()
+                    .set_index_name(g.index_name().map(|s| s.to_string()))
+                    .set_key_schema(Some(g.key_schema().to_vec()))
+                    .set_projection(g.projection().cloned())
+                    .set_provisioned_throughput(gsi_throughput)
+                    .build()?)
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        let local_indexes = t
+            .local_secondary_indexes()
+            .iter()
+            .map(|l| {
+                Ok(LocalSecondaryIndex::builder()
+                    .set_index_name(l.index_name().map(|s| s.to_string()))
+                    .set_key_schema(Some(l.key_schema().to_vec()))
+                    .set_projection(l.projection().cloned())
+                    .build()?)
+            })
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        let tags = tags
+            .into_iter()
+            .map(|(k, v)| Ok(Tag::builder().key(k).value(v).build()?))
+            .collect::<Result<Vec<_>, BoxError>>()?;
+        Ok(TableSchema {
+            table_name: t.table_name().unwrap_or_default().to_string(),
+            attribute_definitions: t.attribute_definitions().to_vec(),
+            key_schema: t.key_schema().to_vec(),
+            global_indexes,
+            local_indexes,
+            billing_mode,
+            throughput,
+            stream: t.stream_specification().filter(|s| s.stream_enabled()).cloned(),
+            ttl_attribute,
+            tags,
+        })
+    }
+
+    /// Normalized document: lists sorted by name, absent settings omitted.
+    pub fn to_json(&self) -> Value {
+        let mut attrs: Vec<&AttributeDefinition> = self.attribute_definitions.iter().collect();
+        attrs.sort_by_key(|a| a.attribute_name());
+        let mut gsis: Vec<&GlobalSecondaryIndex> = self.global_indexes.iter().collect();
+        gsis.sort_by_key(|g| g.index_name());
+        let mut lsis: Vec<&LocalSecondaryIndex> = self.local_indexes.iter().collect();
+        lsis.sort_by_key(|l| l.index_name());
+        let mut tags: Vec<&Tag> = self.tags.iter().collect();
+        tags.sort_by_key(|t| t.key());
+
+        object(vec![
+            ("TableName", json!(self.table_name)),
+            (
+                "AttributeDefinitions",
+                attrs
+                    .iter()
+                    .map(|a| json!({ "AttributeName": a.attribute_name(), "AttributeType": a.attribute_type().as_str() }))
+                    .collect(),
+            ),
+            ("KeySchema", key_schema_to_json(&self.key_schema)),
+            (
+                "GlobalSecondaryIndexes",
+                gsis.iter()
+                    .map(|g| {
+                        object(vec![
+                            ("IndexName", json!(g.index_name())),
+                            ("KeySchema", key_schema_to_json(g.key_schema())),
+                            ("Projection", projection_to_json(g.projection())),
+                            ("ProvisionedThroughput", throughput_to_json(g.provisioned_throughput())),
+                        ])
+                    })
+                    .collect(),
+            ),
+            (
+                "LocalSecondaryIndexes",
+                lsis.iter()
+                    .map(|l| {
+                        object(vec![
+                            ("IndexName", json!(l.index_name())),
+                            ("KeySchema", key_schema_to_json(l.key_schema())),
+                            ("Projection", projection_to_json(l.projection())),
+                        ])
+                    })
+                    .collect(),
+            ),
+            ("BillingMode", json!(self.billing_mode.as_str())),
+            ("ProvisionedThroughput", throughput_to_json(self.throughput.as_ref())),
+            (
+                "StreamSpecification",
+                match &self.stream {
+                    Some(s) => json!({ "StreamEnabled": true, "StreamViewType": s.stream_view_type().map(|v| v.as_str()) }),
+                    None => Value::Null,
+                },
+            ),
+            (
+                "TimeToLive",
+                match &self.ttl_attribute {
+                    Some(a) => json!({ "AttributeName": a, "Enabled": true }),
+                    None => Value::Null,
+                },
+            ),
+            ("Tags", tags.iter().map(|t| json!({ "Key": t.key(), "Value": t.value() })).collect()),
+        ])
+    }
+
+    /// The parts of the schema `table-diff` compares: everything except the
+    /// table name and tags.
+    pub fn comparable_json(&self) -> Value {
+        let mut doc = self.to_json();
+        if let Some(map) = doc.as_object_mut() {
+            map.remove("TableName");
+            map.remove("Tags");
+        }
+        doc
+    }
+}
+
+/// Render a schema document as YAML (for files) or pretty JSON.
+pub fn render(doc: &Value, json: bool) -> Result<String, BoxError> {
+    if json { Ok(format!("{:#}", doc)) } else { Ok(serde_yaml::to_string(doc)?) }
+}
+
+/// Read the schema of an existing table (DescribeTable, DescribeTimeToLive and tags).
+pub async fn schema_from_table(client: &DdbClient, table: &str) -> Result<TableSchema, BoxError> {
+    let resp = client.describe_table().table_name(table).send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+    let t = resp.table().ok_or_else(|| format!("table '{}' has no description", table))?;
+    let ttl = client
+        .describe_time_to_live()
+        .table_name(table)
+        .send()
+        .await
+        .map_err(aws_sdk_dynamodb::Error::from)?;
+    let ttl_attribute = ttl
+        .time_to_live_description()
+        .filter(|d| matches!(d.time_to_live_status(), Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling)))
+        .and_then(|d| d.attribute_name())
+        .map(|s| s.to_string());
+    let tags = list_table_tags(client, t.table_arn().unwrap_or_default()).await?;
+    TableSchema::from_description(t, ttl_attribute, tags)
+}
+
+/// Poll DescribeTable until the table and all of its GSIs are ACTIVE, giving
+/// up with an error after `timeout`.
+pub async fn wait_for_active(client: &DdbClient, table: &str, timeout: Duration) -> Result<(), BoxError> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let resp = client.describe_table().table_name(table).send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+        let t = resp.table().ok_or_else(|| format!("DescribeTable returned no description for '{}'", table))?;
+        let pending: Vec<String> = t
+            .global_secondary_indexes()
+            .iter()
+            .filter(|g| g.index_status() != Some(&IndexStatus::Active))
+            .map(|g| format!("{} {}", g.index_name().unwrap_or_default(), g.index_status().map(|s| s.as_str()).unwrap_or("UNKNOWN")))
+            .collect();
+        if t.table_status() == Some(&TableStatus::Active) && pending.is_empty() {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            let status = t.table_status().map(|s| s.as_str()).unwrap_or("UNKNOWN");
+            let indexes = if pending.is_empty() { String::new() } else { format!(", indexes: {}", pending.join(", ")) };
+            return Err(format!("timed out after {:?} waiting for '{}' to become ACTIVE (table {}{})", timeout, table, status, indexes).into());
+        }
+        tokio::time::sleep(WAIT_POLL_INTERVAL).await;
+    }
+}
+
+/// Enable TTL on `attribute`, or disable the currently enabled `attribute`.
+pub async fn set_ttl(client: &DdbClient, table: &str, attribute: &str, enabled: bool) -> Result<(), aws_sdk_dynamodb::Error> {
+    client
+        .update_time_to_live()
+        .table_name(table)
+        .time_to_live_specification(
+            TimeToLiveSpecification::builder()
+                .enabled(enabled)
+                .attribute_name(attribute)
+                .build()?,
+        )
+        .send()
+        .await?;
+    Ok(())
+}
+
+fn non_empty<T>(v: Vec<T>) -> Option<Vec<T>> {
+    (!v.is_empty()).then_some(v)
+}
+
+/// CreateTable from `schema`, then (when `wait` gives a timeout) wait for
+/// ACTIVE and enable TTL, which DynamoDB only accepts once the table exists.
+/// Without `wait` the schema's TTL is not applied; callers refuse that case.
+pub async fn create_table(client: &DdbClient, schema: &TableSchema, wait: Option<Duration>) -> Result<(), BoxError> {
+    client
+        .create_table()
+        .table_name(&schema.table_name)
+        .set_attribute_definitions(Some(schema.attribute_definitions.clone()))
+        .set_key_schema(Some(schema.key_schema.clone()))
+        .set_global_secondary_indexes(non_empty(schema.global_indexes.clone()))
+        .set_local_secondary_indexes(non_empty(schema.local_indexes.clone()))
+        .billing_mode(schema.billing_mode.clone())
+        .set_provisioned_throughput(schema.throughput.clone())
+        .set_stream_specification(schema.stream.clone())
+        .set_tags(non_empty(schema.tags.clone()))
+        .send()
+        .await
+        .map_err(aws_sdk_dynamodb::Error::from)?;
+    let Some(timeout) = wait else { return Ok(()) };
+    wait_for_active(client, &schema.table_name, timeout).await?;
+    if let Some(attr) = &schema.ttl_attribute {
+        set_ttl(client, &schema.table_name, attr, true).await?;
+    }
+    Ok(())
+}
+
+/// One in-place change `table-diff --apply` can make.
+pub enum SchemaChange {
+    /// Switch billing mode and/or table capacity. Switching to PROVISIONED also
+    /// needs the capacity of every index that stays.
+    Billing { mode: BillingMode, throughput: Option<ProvisionedThroughput>, indexes: Vec<(String, ProvisionedThroughput)> },
+    IndexThroughput { index: String, throughput: ProvisionedThroughput },
+    DeleteIndex(String),
+    CreateIndex(GlobalSecondaryIndex),
+    EnableTtl(String),
+    DisableTtl(String),
+}
+
+impl fmt::Display for SchemaChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let capacity = |p: &ProvisionedThroughput| format!("{} RCU / {} WCU", p.read_capacity_units(), p.write_capacity_units());
+        match self {
+            SchemaChange::Billing { mode, throughput, .. } => match throughput {
+                Some(p) => write!(f, "set billing mode {} ({})", mode.as_str(), capacity(p)),
+                None => write!(f, "set billing mode {}", mode.as_str()),
+            },
+            SchemaChange::IndexThroughput { index, throughput } => write!(f, "set capacity of index {} to {}", index, capacity(throughput)),
+            SchemaChange::DeleteIndex(name) => write!(f, "delete global secondary index {}", name),
+            SchemaChange::CreateIndex(g) => write!(f, "create global secondary index {}", g.index_name()),
+            SchemaChange::EnableTtl(attr) => write!(f, "enable TTL on {}", attr),
+            SchemaChange::DisableTtl(attr) => write!(f, "disable TTL on {}", attr),
+        }
+    }
+}
+
+/// Changes to bring `current` to `desired`, plus the drift that can't be
+/// applied in place (each as a short explanation).
+pub struct SchemaPlan {
+    pub changes: Vec<SchemaChange>,
+    pub skipped: Vec<String>,
+}
+
+fn index_shape(g: &GlobalSecondaryIndex) -> Value {
+    json!([key_schema_to_json(g.key_schema()), projection_to_json(g.projection())])
+}
+
+fn local_indexes_json(schema: &TableSchema) -> Value {
+    schema.comparable_json().get("LocalSecondaryIndexes").cloned().unwrap_or(Value::Null)
+}
+
+pub fn plan_changes(current: &TableSchema, desired: &TableSchema) -> SchemaPlan {
+    let mut changes = Vec::new();
+    let mut skipped = Vec::new();
+
+    if key_schema_to_json(&current.key_schema) != key_schema_to_json(&desired.key_schema) {
+        skipped.push("key schema differs (requires recreating the table)".to_string());
+    }
+    if local_indexes_json(current) != local_indexes_json(desired) {
+        skipped.push("local secondary indexes differ (they can only be defined at creation)".to_string());
+    }
+    if current.stream != desired.stream {
+        skipped.push("stream specification differs (enable/disable it explicitly; consumers see a new stream ARN)".to_string());
+    }
+
+    let current_gsis: BTreeMap<&str, &GlobalSecondaryIndex> = current.global_indexes.iter().map(|g| (g.index_name(), g)).collect();
+    let desired_gsis: BTreeMap<&str, &GlobalSecondaryIndex> = desired.global_indexes.iter().map(|g| (g.index_name(), g)).collect();
+    let provisioned = desired.billing_mode == BillingMode::Provisioned;
+
+    for name in current_gsis.keys().filter(|n| !desired_gsis.contains_key(*n)) {
+        changes.push(SchemaChange::DeleteIndex(name.to_string()));
+    }
+
+    // Indexes present on both sides: same shape, possibly new capacity.
+    let mut kept_capacity = Vec::new();
+    for (name, want) in &desired_gsis {
+        let Some(have) = current_gsis.get(name) else { continue };
+        if index_shape(have) != index_shape(want) {
+            skipped.push(format!("index {} has a different key schema or projection (delete and re-create it)", name));
+            continue;
+        }
+        if provisioned && let Some(p) = want.provisioned_throughput() {
+            kept_capacity.push((name.to_string(), p.clone(), have.provisioned_throughput() != Some(p)));
+        }
+    }
+
+    if current.billing_mode != desired.billing_mode {
+        changes.push(SchemaChange::Billing {
+            mode: desired.billing_mode.clone(),
+            throughput: desired.throughput.clone(),
+            indexes: kept_capacity.iter().map(|(n, p, _)| (n.clone(), p.clone())).collect(),
+        });
+    } else {
+        if provisioned && current.throughput != desired.throughput {
+            changes.push(SchemaChange::Billing { mode: desired.billing_mode.clone(), throughput: desired.throughput.clone(), indexes: Vec::new() });
+        }
+        for (index, throughput, _) in kept_capacity.into_iter().filter(|(_, _, changed)| *changed) {
+            changes.push(SchemaChange::IndexThroughput { index, throughput });
+        }
+    }
+
+    for (name, g) in &desired_gsis {
+        if !current_gsis.contains_key(name) {
+            changes.push(SchemaChange::CreateIndex((*g).clone()));
+        }
+    }
+
+    match (&current.ttl_attribute, &desired.ttl_attribute) {
+        (None, Some(attr)) => changes.push(SchemaChange::EnableTtl(attr.clone())),
+        (Some(attr), None) => changes.push(SchemaChange::DisableTtl(attr.clone())),
+        (Some(a), Some(b)) if a != b => {
+            skipped.push(format!("TTL attribute differs ({} vs {}): disable it, then enable the new one (DynamoDB allows one TTL change per hour)", a, b))
+        }
+        _ => {}
+    }
+
+    SchemaPlan { changes, skipped }
+}
+
+/// Apply one change to `table` and wait until the table (and its indexes)
+/// are ACTIVE again (up to `timeout`). `desired` supplies attribute definitions
+/// for new indexes.
+pub async fn apply_change(
+    client: &DdbClient,
+    table: &str,
+    change: &SchemaChange,
+    desired: &TableSchema,
+    timeout: Duration,
+) -> Result<(), BoxError> {
+    let update = client.update_table().table_name(table);
+    let update = match change {
+        SchemaChange::EnableTtl(attr) => return Ok(set_ttl(client, table, attr, true).await?),
+        SchemaChange::DisableTtl(attr) => return Ok(set_ttl(client, table, attr, false).await?),
+        SchemaChange::Billing { mode, throughput, indexes } => {
+            let index_updates = indexes
+                .iter()
+                .map(|(name, p)| {
+                    Ok(GlobalSecondaryIndexUpdate::builder()
+                        .update(UpdateGlobalSecondaryIndexAction::builder().index_name(name).provisioned_throughput(p.clone()).build()?)
+                        .build())
+                })
+                .collect::<Result<Vec<_>, BoxError>>()?;
+            update
+                .billing_mode(mode.clone())
+                .set_provisioned_throughput(throughput.clone())
+                .set_global_secondary_index_updates(non_empty(index_updates))
+        }
+        SchemaChange::IndexThroughput { index, throughput } => update.global_secondary_index_updates(
+            GlobalSecondaryIndexUpdate::builder()
+                .update(UpdateGlobalSecondaryIndexAction::builder().index_name(index).provisioned_throughput(throughput.clone()).build()?)
+                .build(),
+        ),
+        SchemaChange::DeleteIndex(name) => update.global_secondary_index_updates(
+            GlobalSecondaryIndexUpdate::builder().delete(DeleteGlobalSecondaryIndexAction::builder().index_name(name).build()?).build(),
+        ),
+        SchemaChange::CreateIndex(g) => {
+            // Definitions must cover the new index's keys; send every key
+            // attribute of the desired schema so none of the existing ones drop out.
+            let key_attributes: Vec<&str> = desired
+                .key_schema
+                .iter()
+                .chain(desired.global_indexes.iter().flat_map(|i| i.key_schema()))
+                .chain(desired.local_indexes.iter().flat_map(|i| i.key_schema()))
+                .map(|k| k.attribute_name())
+                .collect();
+            let definitions: Vec<AttributeDefinition> = desired
+                .attribute_definitions
+                .iter()
+                .filter(|a| key_attributes.contains(&a.attribute_name()))
+                .cloned()
+                .collect();
+            update.set_attribute_definitions(Some(definitions)).global_secondary_index_updates(
+                GlobalSecondaryIndexUpdate::builder()
+                    .create(
+                        CreateGlobalSecondaryIndexAction::builder()
+                            .index_name(g.index_name())
+                            .set_key_schema(Some(g.key_schema().to_vec()))
+                            .set_projection(g.projection().cloned())
+                            .set_provisioned_throughput(g.provisioned_throughput().cloned())
+                            .build()?,
+                    )
+                    .build(),
+            )
+        }
+    };
+    update.send().await.map_err(aws_sdk_dynamodb::Error::from)?;
+    wait_for_active(client, table, timeout).await?;
+    Ok(())
+}
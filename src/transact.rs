@@ -0,0 +1,184 @@
+// Transactions from a file (`transact`, `transact-get`).
+//
+// Files use the `--transact-items` shape of `aws dynamodb transact-write-items`
+// / `transact-get-items` (a list, or an object with `TransactItems`), as JSON
+// or YAML, with items, keys and expression values in DynamoDB JSON:
+//
+//   - Put: {TableName: orders, Item: {pk: {S: a}, n: {N: "1"}}, ConditionExpression: ...}
+//   - Update: {TableName: orders, Key: {...}, UpdateExpression: "SET n = n + :one", ...}
+//   - Delete: {TableName: orders, Key: {...}}
+//   - ConditionCheck: {TableName: stock, Key: {...}, ConditionExpression: "qty > :zero", ...}
+//   - Get: {TableName: orders, Key: {...}, ProjectionExpression: ...}   (transact-get)
+
+use crate::dynamodb::{item_from_json, item_to_json};
+use crate::json_doc::{field, load_document, str_field, string_map_field};
+use aws_sdk_dynamodb::Client as DdbClient;
+use aws_sdk_dynamodb::error::SdkError;
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, ConditionCheck, Delete, Get, Put, ReturnValuesOnConditionCheckFailure, TransactGetItem, TransactWriteItem,
+    Update,
+};
+use serde_json::{Value, json};
+use std::collections::HashMap;
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+type Item = HashMap<String, AttributeValue>;
+
+/// The operations of a transaction file, each with a short label for reports.
+fn operations(path: &str) -> Result<Vec<(String, Value)>, BoxError> {
+    let doc = load_document(path)?;
+    let list = field(&doc, "TransactItems").unwrap_or(&doc);
+    let list = list.as_array().ok_or("expected a list of operations (or {\"TransactItems\": [...]})")?;
+    list.iter()
+        .enumerate()
+        .map(|(i, op)| {
+            let (kind, body) = op
+                .as_object()
+                .filter(|o| o.len() == 1)
+                .and_then(|o| o.iter().next())
+                .ok_or_else(|| format!("operation {} must have exactly one of Put, Update, Delete, ConditionCheck or Get", i + 1))?;
+            let table = str_field(body, "TableName")?.unwrap_or_else(|| "?".to_string());
+            Ok((format!("{} {}", kind, table), op.clone()))
+        })
+        .collect()
+}
+
+/// An item, key or value map in DynamoDB JSON.
+fn item_field(v: &Value, name: &str) -> Result<Option<Item>, String> {
+    field(v, name).map(item_from_json).transpose()
+}
+
+fn return_values(v: &Value) -> Result<Option<ReturnValuesOnConditionCheckFailure>, BoxError> {
+    Ok(str_field(v, "ReturnValuesOnConditionCheckFailure")?.map(|s| ReturnValuesOnConditionCheckFailure::from(s.as_str())))
+}
+
+fn write_item(op: &Value) -> Result<TransactWriteItem, BoxError> {
+    let builder = TransactWriteItem::builder();
+    let builder = if let Some(b) = op.get("Put") {
+        builder.put(
+            Put::builder()
+                .set_table_name(str_field(b, "TableName")?)
+                .set_item(item_field(b, "Item")?)
+                .set_condition_expression(str_field(b, "ConditionExpression")?)
+                .set_expression_attribute_names(string_map_field(b, "ExpressionAttributeNames")?)
+                .set_expression_attribute_values(item_field(b, "ExpressionAttributeValues")?)
+                .set_return_values_on_condition_check_failure(return_values(b)?)
+                .build()?,
+        )
+    } else if let Some(b) = op.get("Update") {
+        builder.update(
+            Update::builder()
+                .set_table_name(str_field(b, "TableName")?)
+                .set_key(item_field(b, "Key")?)
+                .set_update_expression(str_field(b, "UpdateExpression")?)
+                .set_condition_expression(str_field(b, "ConditionExpression")?)
+                .set_expression_attribute_names(string_map_field(b, "ExpressionAttributeNames")?)
+                .set_expression_attribute_values(item_field(b, "ExpressionAttributeValues")?)
+                .set_return_values_on_condition_check_failure(return_values(b)?)
+                .build()?,
+        )
+    } else if let Some(b) = op.get("Delete") {
+        builder.delete(
+            Delete::builder()
+                .set_table_name(str_field(b, "TableName")?)
+                .set_key(item_field(b, "Key")?)
+                .set_condition_expression(str_field(b, "ConditionExpression")?)
+                .set_expression_attribute_names(string_map_field(b, "ExpressionAttributeNames")?)
+                .set_expression_attribute_values(item_field(b, "ExpressionAttributeValues")?)
+                .set_return_values_on_condition_check_failure(return_values(b)?)
+                .build()?,
+        )
+    } else if let Some(b) = op.get("ConditionCheck") {
+        builder.condition_check(
+            ConditionCheck::builder()
+                .set_table_name(str_field(b, "TableName")?)
+                .set_key(item_field(b, "Key")?)
+                .set_condition_expression(str_field(b, "ConditionExpression")?)
+                .set_expression_attribute_names(string_map_field(b, "ExpressionAttributeNames")?)
+                .set_expression_attribute_values(item_field(b, "ExpressionAttributeValues")?)
+                .set_return_values_on_condition_check_failure(return_values(b)?)
+                .build()?,
+        )
+    } else {
+        return Err("transact accepts Put, Update, Delete and ConditionCheck operations".into());
+    };
+    Ok(builder.build())
+}
+
+/// Run the operations in `path` atomically with TransactWriteItems. If the
+/// transaction is cancelled, each operation's reason is printed on stderr
+/// before the error is returned.
+pub async fn transact_write(client: &DdbClient, path: &str, token: Option<&str>) -> Result<usize, BoxError> {
+    let ops = operations(path)?;
+    let items = ops
+        .iter()
+        .enumerate()
+        .map(|(i, (label, op))| write_item(op).map_err(|e| format!("operation {} ({}): {}", i + 1, label, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    let result = client
+        .transact_write_items()
+        .set_transact_items(Some(items))
+        .set_client_request_token(token.map(|t| t.to_string()))
+        .send()
+        .await;
+    let err = match result {
+        Ok(_) => return Ok(ops.len()),
+        Err(SdkError::ServiceError(e)) => e.into_err(),
+        Err(e) => return Err(aws_sdk_dynamodb::Error::from(e).into()),
+    };
+    match err {
+        TransactWriteItemsError::TransactionCanceledException(cancelled) => {
+            eprintln!("Transaction cancelled:");
+            for (i, ((label, _), reason)) in ops.iter().zip(cancelled.cancellation_reasons()).enumerate() {
+                let code = reason.code().unwrap_or("None");
+                if code == "None" {
+                    eprintln!("  {}. {}: ok", i + 1, label);
+                    continue;
+                }
+                eprintln!("  {}. {}: {}{}", i + 1, label, code, reason.message().map(|m| format!(" ({})", m)).unwrap_or_default());
+                if let Some(item) = reason.item() {
+                    eprintln!("     current item: {}", item_to_json(item));
+                }
+            }
+            Err("transaction cancelled; no changes were made".into())
+        }
+        other => Err(aws_sdk_dynamodb::Error::from(other).into()),
+    }
+}
+
+/// Read the items named in `path` in one consistent snapshot with
+/// TransactGetItems; results line up with the requests (None when missing).
+pub async fn transact_get(client: &DdbClient, path: &str) -> Result<Vec<(String, Option<Item>)>, BoxError> {
+    let ops = operations(path)?;
+    let gets = ops
+        .iter()
+        .enumerate()
+        .map(|(i, (label, op))| {
+            let b = op.get("Get").ok_or_else(|| format!("operation {} ({}): transact-get accepts only Get operations", i + 1, label))?;
+            Ok(TransactGetItem::builder()
+                .get(
+                    Get::builder()
+                        .set_table_name(str_field(b, "TableName")?)
+                        .set_key(item_field(b, "Key")?)
+                        .set_projection_expression(str_field(b, "ProjectionExpression")?)
+                        .set_expression_attribute_names(string_map_field(b, "ExpressionAttributeNames")?)
+                        .build()?,
+                )
+                .build())
+        })
+        .collect::<Result<Vec<_>, BoxError>>()?;
+    let tables: Vec<String> = gets.iter().filter_map(|g| g.get()).map(|g| g.table_name().to_string()).collect();
+    let resp = client
+        .transact_get_items()
+        .set_transact_items(Some(gets))
+        .send()
+        .await
+        .map_err(aws_sdk_dynamodb::Error::from)?;
+    Ok(tables.into_iter().zip(resp.responses.unwrap_or_default()).map(|(t, r)| (t, r.item)).collect())
+}
+
+/// `transact-get --format json`: one entry per request, `Item` null when missing.
+pub fn get_results_json(results: &[(String, Option<Item>)]) -> Value {
+    results.iter().map(|(table, item)| json!({ "TableName": table, "Item": item.as_ref().map(item_to_json) })).collect()
+}